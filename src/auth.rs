@@ -0,0 +1,340 @@
+use anyhow::Result;
+use regex::Regex;
+use std::path::Path;
+
+use crate::template::OutputDir;
+use crate::vfs::FileSystem;
+
+/// Where templates keep one Auth.js provider config per file, e.g.
+/// `src/lib/auth-providers/github.ts`, plus an `index` that collects them.
+pub const PROVIDERS_DIR: &str = "src/lib/auth-providers";
+
+/// Dev server port assumed when the template doesn't set one.
+pub const DEFAULT_DEV_PORT: u16 = 3000;
+
+/// Auth.js config, in either language.
+const AUTH_CONFIGS: &[&str] = &["src/lib/auth.ts", "src/lib/auth.js"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AuthProvider {
+    GitHub,
+    Google,
+    Discord,
+    /// Magic links sent by email through Nodemailer.
+    Email,
+    /// Username and password checked by your own code.
+    Credentials,
+}
+
+impl AuthProvider {
+    pub const ALL: [AuthProvider; 5] = [
+        AuthProvider::GitHub,
+        AuthProvider::Google,
+        AuthProvider::Discord,
+        AuthProvider::Email,
+        AuthProvider::Credentials,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthProvider::GitHub => "github",
+            AuthProvider::Google => "google",
+            AuthProvider::Discord => "discord",
+            AuthProvider::Email => "email",
+            AuthProvider::Credentials => "credentials",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            AuthProvider::GitHub => "GitHub OAuth",
+            AuthProvider::Google => "Google OAuth",
+            AuthProvider::Discord => "Discord OAuth",
+            AuthProvider::Email => "Email magic link",
+            AuthProvider::Credentials => "Credentials",
+        }
+    }
+
+    /// `.env` keys the provider reads.
+    pub fn env_keys(&self) -> &'static [&'static str] {
+        match self {
+            AuthProvider::GitHub => &["AUTH_GITHUB_ID", "AUTH_GITHUB_SECRET"],
+            AuthProvider::Google => &["AUTH_GOOGLE_ID", "AUTH_GOOGLE_SECRET"],
+            AuthProvider::Discord => &["AUTH_DISCORD_ID", "AUTH_DISCORD_SECRET"],
+            AuthProvider::Email => &["AUTH_EMAIL_SERVER", "AUTH_EMAIL_FROM"],
+            AuthProvider::Credentials => &[],
+        }
+    }
+
+    /// npm packages as `(name, version, dev)`, on top of `@auth/core`.
+    pub fn npm_packages(&self) -> &'static [(&'static str, &'static str, bool)] {
+        match self {
+            AuthProvider::Email => &[("nodemailer", "^7.0.0", false), ("@types/nodemailer", "^6.4.0", true)],
+            _ => &[],
+        }
+    }
+
+    /// OAuth callback to register with the provider, for OAuth providers.
+    /// `origin` is the dev server's, see [`dev_origin`].
+    pub fn callback_url(&self, origin: &str) -> Option<String> {
+        match self {
+            AuthProvider::GitHub | AuthProvider::Google | AuthProvider::Discord => {
+                Some(format!("{origin}/api/auth/callback/{}", self.as_str()))
+            }
+            AuthProvider::Email | AuthProvider::Credentials => None,
+        }
+    }
+
+    /// Built-in provider config, used when the template has none.
+    fn module(&self) -> String {
+        let (name, path) = match self {
+            AuthProvider::GitHub => ("GitHub", "github"),
+            AuthProvider::Google => ("Google", "google"),
+            AuthProvider::Discord => ("Discord", "discord"),
+            AuthProvider::Email => ("Nodemailer", "nodemailer"),
+            AuthProvider::Credentials => ("Credentials", "credentials"),
+        };
+        let options = match self {
+            AuthProvider::Credentials => "  credentials: { email: {}, password: {} },\n  \
+                // Look the user up and check the password; return null to reject\n  \
+                authorize: async () => null,\n"
+                .to_string(),
+            AuthProvider::Email => "  server: process.env.AUTH_EMAIL_SERVER,\n  \
+                from: process.env.AUTH_EMAIL_FROM,\n"
+                .to_string(),
+            _ => {
+                let keys = self.env_keys();
+                format!(
+                    "  clientId: process.env.{},\n  clientSecret: process.env.{},\n",
+                    keys[0], keys[1]
+                )
+            }
+        };
+        format!("import {name} from \"@auth/core/providers/{path}\"\n\nexport default {name}({{\n{options}}})\n")
+    }
+}
+
+/// Provider files to leave out of the copy: the ones that weren't picked and
+/// the index, which is generated for the picked ones.
+pub fn provider_skips(providers: &[AuthProvider]) -> Vec<String> {
+    let mut skip = vec![format!("{PROVIDERS_DIR}/index.ts")];
+    for provider in AuthProvider::ALL.iter().filter(|p| !providers.contains(p)) {
+        skip.push(format!("{PROVIDERS_DIR}/{}.ts", provider.as_str()));
+    }
+    skip
+}
+
+/// Origin the app's dev server listens on: the `server.port` in the Vite
+/// config, else a `--port` on the `dev` script, else [`DEFAULT_DEV_PORT`].
+pub fn dev_origin(fs: &dyn FileSystem, app_dir: &Path) -> String {
+    let vite_port = ["vite.config.ts", "vite.config.js", "vite.config.mts", "vite.config.mjs"]
+        .iter()
+        .find_map(|name| fs.read_to_string(&app_dir.join(name)).ok())
+        .and_then(|config| capture_port(r"\bport\s*:\s*(\d+)", &config));
+    let script_port = || {
+        let package = fs.read_to_string(&app_dir.join("package.json")).ok()?;
+        let package: serde_json::Value = serde_json::from_str(&package).ok()?;
+        capture_port(r"--port[=\s]+(\d+)", package["scripts"]["dev"].as_str()?)
+    };
+    let port = vite_port.or_else(script_port).unwrap_or(DEFAULT_DEV_PORT);
+    format!("http://localhost:{port}")
+}
+
+fn capture_port(pattern: &str, text: &str) -> Option<u16> {
+    Regex::new(pattern).ok()?.captures(text)?[1].parse().ok()
+}
+
+/// Write a config for every picked provider the template didn't ship and an
+/// index exporting them as `providers`. The GitHub-only default is left to
+/// the template unless its Auth.js config loads the index. Returns warnings
+/// for providers the config can't use as it stands.
+pub fn write_provider_files(
+    output: &mut OutputDir,
+    app_dir: &Path,
+    providers: &[AuthProvider],
+    typescript: bool,
+) -> Result<Vec<String>> {
+    let config = AUTH_CONFIGS
+        .iter()
+        .map(|path| app_dir.join(path))
+        .find(|path| output.fs().exists(path));
+    let config = match config {
+        Some(path) => Some(output.fs().read_to_string(&path)?),
+        None => None,
+    };
+    let loads_index = config.as_ref().is_some_and(|config| config.contains("auth-providers"));
+    if providers == [AuthProvider::GitHub] && !loads_index {
+        return Ok(Vec::new());
+    }
+
+    let ext = if typescript { "ts" } else { "js" };
+    let dir = app_dir.join(PROVIDERS_DIR);
+    output.fs().create_dir_all(&dir)?;
+
+    let mut imports = String::new();
+    let mut names = Vec::new();
+    for provider in providers {
+        let name = provider.as_str();
+        let shipped = ["ts", "js"]
+            .iter()
+            .any(|e| output.fs().exists(&dir.join(format!("{name}.{e}"))));
+        if !shipped {
+            let dest = dir.join(format!("{name}.{ext}"));
+            if output.prepare_write(&dest)? {
+                output.fs().write(&dest, provider.module().as_bytes())?;
+            }
+        }
+        imports.push_str(&format!("import {name} from \"./{name}\"\n"));
+        names.push(name);
+    }
+
+    let index = dir.join(format!("index.{ext}"));
+    if output.prepare_write(&index)? {
+        let contents = format!("{imports}\nexport const providers = [{}]\n", names.join(", "));
+        output.fs().write(&index, contents.as_bytes())?;
+    }
+
+    let mut warnings = Vec::new();
+    let Some(config) = config else {
+        return Ok(warnings);
+    };
+    if !loads_index {
+        warnings.push(format!(
+            "The template's Auth.js config doesn't load {PROVIDERS_DIR}; add the providers you picked to src/lib/auth by hand"
+        ));
+    }
+    if providers.contains(&AuthProvider::Email) && !config.contains("adapter") {
+        warnings.push(
+            "Email magic links need a database adapter to store verification tokens; set `adapter` in src/lib/auth"
+                .to_string(),
+        );
+    }
+    if providers.contains(&AuthProvider::Credentials) && !config.contains("\"jwt\"") {
+        warnings.push(
+            "The Credentials provider only works with JWT sessions; set `session: { strategy: \"jwt\" }` in src/lib/auth"
+                .to_string(),
+        );
+    }
+    Ok(warnings)
+}
+
+/// The `.env` section for auth: the secret, then each provider's keys.
+pub fn env_section(secret: &str, providers: &[AuthProvider]) -> String {
+    let mut env = format!("# Auth\nAUTH_SECRET={secret}\n");
+    for provider in providers {
+        if provider.env_keys().is_empty() {
+            continue;
+        }
+        env.push_str(&format!("\n# {}\n", provider.label()));
+        for key in provider.env_keys() {
+            env.push_str(&format!("{key}=\n"));
+        }
+    }
+    env
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::template::WriteMode;
+    use crate::vfs::{FileSystem, MemoryFs};
+
+    #[test]
+    fn env_section_lists_keys_per_provider() {
+        let env = env_section("s3cret", &[AuthProvider::Google, AuthProvider::Credentials, AuthProvider::Email]);
+        assert!(env.starts_with("# Auth\nAUTH_SECRET=s3cret\n"), "{env}");
+        assert!(env.contains("# Google OAuth\nAUTH_GOOGLE_ID=\nAUTH_GOOGLE_SECRET=\n"), "{env}");
+        assert!(env.contains("AUTH_EMAIL_SERVER=\nAUTH_EMAIL_FROM=\n"), "{env}");
+        assert!(!env.contains("GITHUB"), "{env}");
+    }
+
+    #[test]
+    fn callback_urls_only_for_oauth() {
+        assert_eq!(
+            AuthProvider::Discord.callback_url("http://localhost:3000").as_deref(),
+            Some("http://localhost:3000/api/auth/callback/discord")
+        );
+        assert_eq!(AuthProvider::Email.callback_url("http://localhost:3000"), None);
+    }
+
+    #[test]
+    fn dev_origin_reads_the_template_port() {
+        let mut mem = MemoryFs::new();
+        mem.create_dir_all(Path::new("app")).unwrap();
+        assert_eq!(dev_origin(&mem, Path::new("app")), "http://localhost:3000");
+        mem.write(Path::new("app/package.json"), br#"{"scripts":{"dev":"vite dev --port 5000"}}"#)
+            .unwrap();
+        assert_eq!(dev_origin(&mem, Path::new("app")), "http://localhost:5000");
+        mem.write(Path::new("app/vite.config.ts"), b"server: { port: 4173 },\n").unwrap();
+        assert_eq!(dev_origin(&mem, Path::new("app")), "http://localhost:4173");
+    }
+
+    #[test]
+    fn github_only_default_is_left_to_the_template() {
+        let mut mem = MemoryFs::new();
+        mem.create_dir_all(Path::new("app/src/lib")).unwrap();
+        mem.write(Path::new("app/src/lib/auth.ts"), b"import GitHub from \"@auth/core/providers/github\"\n").unwrap();
+        let mut output = OutputDir::with_fs(Path::new("app"), WriteMode::Create, &mut mem);
+        let warnings = write_provider_files(&mut output, Path::new("app"), &[AuthProvider::GitHub], true).unwrap();
+        drop(output);
+
+        assert!(warnings.is_empty(), "{warnings:?}");
+        assert!(!mem.exists(Path::new("app/src/lib/auth-providers")));
+    }
+
+    #[test]
+    fn warns_when_the_config_cannot_serve_email_or_credentials() {
+        let mut mem = MemoryFs::new();
+        mem.create_dir_all(Path::new("app/src/lib")).unwrap();
+        mem.write(Path::new("app/src/lib/auth.ts"), b"import { providers } from \"./auth-providers\"\n").unwrap();
+        let mut output = OutputDir::with_fs(Path::new("app"), WriteMode::Create, &mut mem);
+        let providers = [AuthProvider::Email, AuthProvider::Credentials];
+        let warnings = write_provider_files(&mut output, Path::new("app"), &providers, true).unwrap();
+        drop(output);
+        assert_eq!(warnings.len(), 2, "{warnings:?}");
+        assert!(warnings[0].contains("database adapter"), "{warnings:?}");
+        assert!(warnings[1].contains("JWT sessions"), "{warnings:?}");
+
+        mem.write(
+            Path::new("app/src/lib/auth.ts"),
+            b"import { providers } from \"./auth-providers\"\nadapter: PgAdapter(pool),\nsession: { strategy: \"jwt\" },\n",
+        )
+        .unwrap();
+        let mut output = OutputDir::with_fs(Path::new("app"), WriteMode::Force, &mut mem);
+        let warnings = write_provider_files(&mut output, Path::new("app"), &providers, true).unwrap();
+        assert!(warnings.is_empty(), "{warnings:?}");
+    }
+
+    #[test]
+    fn provider_files_keep_template_configs() {
+        let mut mem = MemoryFs::new();
+        mem.create_dir_all(Path::new("app/src/lib/auth-providers")).unwrap();
+        mem.write(Path::new("app/src/lib/auth-providers/google.ts"), b"// ours\n").unwrap();
+        mem.write(Path::new("app/src/lib/auth.ts"), b"import GitHub from \"@auth/core/providers/github\"\n").unwrap();
+        let mut output = OutputDir::with_fs(Path::new("app"), WriteMode::Create, &mut mem);
+        let warnings = write_provider_files(
+            &mut output,
+            Path::new("app"),
+            &[AuthProvider::GitHub, AuthProvider::Google],
+            true,
+        )
+        .unwrap();
+        drop(output);
+
+        assert_eq!(warnings.len(), 1, "{warnings:?}");
+        let dir = Path::new("app/src/lib/auth-providers");
+        assert_eq!(mem.read_to_string(&dir.join("google.ts")).unwrap(), "// ours\n");
+        let github = mem.read_to_string(&dir.join("github.ts")).unwrap();
+        assert!(github.contains("process.env.AUTH_GITHUB_SECRET"), "{github}");
+        let index = mem.read_to_string(&dir.join("index.ts")).unwrap();
+        assert!(index.contains("export const providers = [github, google]"), "{index}");
+    }
+
+    #[test]
+    fn skips_unpicked_providers_and_index() {
+        let skip = provider_skips(&[AuthProvider::GitHub]);
+        assert!(skip.contains(&"src/lib/auth-providers/index.ts".to_string()));
+        assert!(skip.contains(&"src/lib/auth-providers/google.ts".to_string()));
+        assert!(!skip.iter().any(|s| s.contains("github")));
+    }
+}
@@ -0,0 +1,365 @@
+use anyhow::{Context, Result};
+use regex::Regex;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use walkdir::WalkDir;
+
+use crate::supervisor::Proc;
+
+/// Where task outputs are kept, relative to the project root.
+pub const CACHE_DIR: &str = ".nullslate/cache";
+
+/// Directories never read as inputs.
+const IGNORED_DIRS: [&str; 4] = [".git", "node_modules", "target", ".nullslate"];
+
+/// Bumped when the key derivation changes, so old entries stop matching.
+const KEY_VERSION: &str = "2";
+
+/// Shell variables that change what builds produce without being in any
+/// env file, such as Vite's public variables and cargo's settings.
+const KEY_SHELL_VARS: [&str; 5] = ["VITE_", "NODE_ENV", "RUSTFLAGS", "RUSTDOCFLAGS", "CARGO_"];
+
+/// Entries kept in the cache; the least recently used go first.
+const MAX_ENTRIES: usize = 64;
+
+/// Caching for one task: the files it reads and writes, as globs relative
+/// to `base`. Inputs starting with `!` exclude files.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct TaskCache {
+    pub root: PathBuf,
+    pub base: PathBuf,
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
+}
+
+/// How a cache lookup went.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Lookup {
+    /// The outputs in place already match the cached ones.
+    Fresh,
+    /// The outputs were copied back from the cache.
+    Restored,
+    Miss,
+}
+
+/// A glob such as `src/**/*.ts`. A pattern without wildcards also matches
+/// everything below it, so `dist` covers the whole directory.
+struct Glob(Regex);
+
+impl Glob {
+    fn new(pattern: &str) -> Result<Glob> {
+        let pattern = pattern.trim_start_matches("./").trim_end_matches('/');
+        let mut regex = String::from("^");
+        let mut chars = pattern.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '*' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    if chars.peek() == Some(&'/') {
+                        chars.next();
+                        regex.push_str("(?:.*/)?");
+                    } else {
+                        regex.push_str(".*");
+                    }
+                }
+                '*' => regex.push_str("[^/]*"),
+                '?' => regex.push_str("[^/]"),
+                c => regex.push_str(&regex::escape(&c.to_string())),
+            }
+        }
+        if !pattern.contains(['*', '?']) {
+            regex.push_str("(?:/.*)?");
+        }
+        regex.push('$');
+        Ok(Glob(Regex::new(&regex).with_context(|| format!("Invalid glob {pattern:?}"))?))
+    }
+
+    fn matches(&self, path: &str) -> bool {
+        self.0.is_match(path)
+    }
+}
+
+/// Files under `base` matching `patterns`, as sorted `/`-separated paths.
+/// Patterns starting with `!` exclude, as do the `exclude` globs.
+fn matching_files(base: &Path, patterns: &[String], exclude: &[String], skip_ignored: bool) -> Result<Vec<String>> {
+    let mut include = Vec::new();
+    let mut excluded = Vec::new();
+    for pattern in patterns {
+        match pattern.strip_prefix('!') {
+            Some(negated) => excluded.push(Glob::new(negated)?),
+            None => include.push(Glob::new(pattern)?),
+        }
+    }
+    for pattern in exclude {
+        excluded.push(Glob::new(pattern)?);
+    }
+    if include.is_empty() || !base.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut files = Vec::new();
+    let walker = WalkDir::new(base).min_depth(1).into_iter().filter_entry(|entry| {
+        !(skip_ignored
+            && entry.file_type().is_dir()
+            && IGNORED_DIRS.contains(&entry.file_name().to_string_lossy().as_ref()))
+    });
+    for entry in walker {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let relative = entry.path().strip_prefix(base)?.to_string_lossy().replace('\\', "/");
+        if include.iter().any(|g| g.matches(&relative)) && !excluded.iter().any(|g| g.matches(&relative)) {
+            files.push(relative);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn file_hash(path: &Path) -> Result<String> {
+    let contents = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(hex::encode(Sha256::digest(contents)))
+}
+
+impl TaskCache {
+    /// The cache key of a run of `steps`: their commands, directories and
+    /// environment, the shell variables builds read, and the contents of
+    /// every input file.
+    pub fn key(&self, name: &str, steps: &[Proc]) -> Result<String> {
+        self.key_with_shell(name, steps, std::env::vars())
+    }
+
+    fn key_with_shell(
+        &self,
+        name: &str,
+        steps: &[Proc],
+        shell: impl Iterator<Item = (String, String)>,
+    ) -> Result<String> {
+        let mut hasher = Sha256::new();
+        let mut field = |value: &str| {
+            hasher.update(value.as_bytes());
+            hasher.update([0]);
+        };
+        field(KEY_VERSION);
+        field(name);
+        for step in steps {
+            field(&step.program);
+            for arg in &step.args {
+                field(arg);
+            }
+            field(&step.cwd.strip_prefix(&self.root).unwrap_or(&step.cwd).to_string_lossy());
+            let mut envs = step.envs.clone();
+            envs.sort();
+            for (key, value) in &envs {
+                field(&format!("{key}={value}"));
+            }
+        }
+        let mut shell: Vec<(String, String)> = shell
+            .filter(|(key, _)| KEY_SHELL_VARS.iter().any(|prefix| key.starts_with(prefix)))
+            .collect();
+        shell.sort();
+        for (key, value) in &shell {
+            field(&format!("{key}={value}"));
+        }
+        for file in matching_files(&self.base, &self.inputs, &self.outputs, true)? {
+            field(&file);
+            field(&file_hash(&self.base.join(&file))?);
+        }
+        Ok(hex::encode(hasher.finalize()))
+    }
+
+    fn entry(&self, key: &str) -> PathBuf {
+        self.root.join(CACHE_DIR).join(key)
+    }
+
+    /// Mark the entry for `key` as just used, for eviction.
+    fn touch(&self, key: &str) -> Result<()> {
+        let listing = std::fs::OpenOptions::new().append(true).open(self.entry(key).join("outputs"))?;
+        listing.set_modified(SystemTime::now())?;
+        Ok(())
+    }
+
+    /// Remove the least recently used entries beyond [`MAX_ENTRIES`].
+    fn evict(&self) -> Result<()> {
+        let mut entries: Vec<(SystemTime, PathBuf)> = std::fs::read_dir(self.root.join(CACHE_DIR))?
+            .flatten()
+            .filter(|entry| entry.path().extension().is_none() && entry.path().is_dir())
+            .map(|entry| {
+                let used = std::fs::metadata(entry.path().join("outputs"))
+                    .and_then(|meta| meta.modified())
+                    .unwrap_or(SystemTime::UNIX_EPOCH);
+                (used, entry.path())
+            })
+            .collect();
+        if entries.len() <= MAX_ENTRIES {
+            return Ok(());
+        }
+        entries.sort();
+        for (_, path) in &entries[..entries.len() - MAX_ENTRIES] {
+            std::fs::remove_dir_all(path)?;
+        }
+        Ok(())
+    }
+
+    /// Put the outputs for `key` in place, if the cache has them.
+    pub fn lookup(&self, key: &str) -> Result<Lookup> {
+        let entry = self.entry(key);
+        let Ok(listing) = std::fs::read_to_string(entry.join("outputs")) else {
+            return Ok(Lookup::Miss);
+        };
+        let outputs: Vec<(&str, &str)> = listing
+            .lines()
+            .filter_map(|line| line.split_once("  "))
+            .collect();
+
+        self.touch(key)?;
+        let fresh = outputs.iter().all(|(hash, file)| {
+            file_hash(&self.base.join(file)).is_ok_and(|current| current == *hash)
+        });
+        if fresh {
+            return Ok(Lookup::Fresh);
+        }
+        for (_, file) in outputs {
+            let dest = self.base.join(file);
+            if let Some(parent) = dest.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::copy(entry.join("files").join(file), &dest)
+                .with_context(|| format!("Failed to restore {} from the cache", dest.display()))?;
+        }
+        Ok(Lookup::Restored)
+    }
+
+    /// Save the current outputs under `key`, evicting old entries.
+    pub fn store(&self, key: &str) -> Result<()> {
+        let cache_dir = self.root.join(CACHE_DIR);
+        std::fs::create_dir_all(&cache_dir)?;
+        let ignore = cache_dir.join(".gitignore");
+        if !ignore.exists() {
+            std::fs::write(&ignore, "*\n")?;
+        }
+        let entry = self.entry(key);
+        let staging = entry.with_extension("tmp");
+        if staging.exists() {
+            std::fs::remove_dir_all(&staging)?;
+        }
+        std::fs::create_dir_all(staging.join("files"))?;
+
+        let mut listing = String::new();
+        for file in matching_files(&self.base, &self.outputs, &[], false)? {
+            let source = self.base.join(&file);
+            let dest = staging.join("files").join(&file);
+            std::fs::create_dir_all(dest.parent().unwrap())?;
+            std::fs::copy(&source, &dest)?;
+            listing.push_str(&format!("{}  {file}\n", file_hash(&source)?));
+        }
+        std::fs::write(staging.join("outputs"), listing)?;
+
+        if entry.exists() {
+            std::fs::remove_dir_all(&entry)?;
+        }
+        std::fs::rename(&staging, &entry)?;
+        self.evict()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn globs(pattern: &str, paths: &[&str]) -> Vec<bool> {
+        let glob = Glob::new(pattern).unwrap();
+        paths.iter().map(|p| glob.matches(p)).collect()
+    }
+
+    #[test]
+    fn glob_patterns() {
+        assert_eq!(globs("**/*.rs", &["main.rs", "src/lib.rs", "src/a.ts"]), [true, true, false]);
+        assert_eq!(globs("src/*.ts", &["src/a.ts", "src/x/a.ts"]), [true, false]);
+        assert_eq!(globs("dist", &["dist/index.html", "dist/a/b.js", "distx"]), [true, true, false]);
+        assert_eq!(globs("Cargo.toml", &["Cargo.toml", "api/Cargo.toml"]), [true, false]);
+    }
+
+    fn write(path: &Path, contents: &str) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn key_follows_inputs_and_store_restores_outputs() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write(&root.join("src/main.ts"), "one");
+        write(&root.join("node_modules/x/index.js"), "ignored");
+        write(&root.join("notes.md"), "excluded");
+        let cache = TaskCache {
+            root: root.to_path_buf(),
+            base: root.to_path_buf(),
+            inputs: vec!["**/*".to_string(), "!*.md".to_string()],
+            outputs: vec!["dist".to_string()],
+        };
+
+        let key = cache.key("build", &[]).unwrap();
+        write(&root.join("notes.md"), "changed");
+        write(&root.join("node_modules/x/index.js"), "changed");
+        write(&root.join("dist/app.js"), "built");
+        assert_eq!(cache.key("build", &[]).unwrap(), key, "excluded files and outputs don't count");
+        assert_ne!(cache.key("test", &[]).unwrap(), key);
+
+        assert_eq!(cache.lookup(&key).unwrap(), Lookup::Miss);
+        cache.store(&key).unwrap();
+        assert_eq!(cache.lookup(&key).unwrap(), Lookup::Fresh);
+        std::fs::remove_dir_all(root.join("dist")).unwrap();
+        assert_eq!(cache.lookup(&key).unwrap(), Lookup::Restored);
+        assert_eq!(std::fs::read_to_string(root.join("dist/app.js")).unwrap(), "built");
+
+        write(&root.join("src/main.ts"), "two");
+        assert_ne!(cache.key("build", &[]).unwrap(), key);
+        assert_eq!(std::fs::read_to_string(root.join(CACHE_DIR).join(".gitignore")).unwrap(), "*\n");
+    }
+
+    #[test]
+    fn key_follows_build_shell_vars() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = TaskCache {
+            root: dir.path().to_path_buf(),
+            base: dir.path().to_path_buf(),
+            inputs: vec!["**/*".to_string()],
+            outputs: Vec::new(),
+        };
+        let key = |vars: &[(&str, &str)]| {
+            let vars = vars.iter().map(|(k, v)| (k.to_string(), v.to_string()));
+            cache.key_with_shell("build", &[], vars.collect::<Vec<_>>().into_iter()).unwrap()
+        };
+        let base = key(&[("HOME", "/home/a")]);
+        assert_eq!(key(&[("HOME", "/home/b")]), base);
+        assert_ne!(key(&[("VITE_API_URL", "https://x")]), base);
+        assert_ne!(key(&[("RUSTFLAGS", "-C target-cpu=native")]), base);
+    }
+
+    #[test]
+    fn least_recently_used_entries_are_evicted() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = TaskCache {
+            root: dir.path().to_path_buf(),
+            base: dir.path().to_path_buf(),
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+        };
+        for i in 0..MAX_ENTRIES {
+            cache.store(&format!("key{i}")).unwrap();
+            let listing = cache.entry(&format!("key{i}")).join("outputs");
+            let listing = std::fs::File::options().append(true).open(listing).unwrap();
+            listing.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(i as u64 + 1)).unwrap();
+        }
+        // Using the oldest keeps it; the next oldest goes instead
+        assert_eq!(cache.lookup("key0").unwrap(), Lookup::Fresh);
+        cache.store("new").unwrap();
+        assert!(cache.entry("key0").exists());
+        assert!(!cache.entry("key1").exists());
+        assert!(cache.entry("new").exists());
+    }
+}
@@ -1,34 +1,237 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+use nullslate_cli::{AuthProvider, Database, Language, Orm, PackageManager, VersionPolicy};
 use std::path::PathBuf;
 
-const DEFAULT_TEMPLATE_REPO: &str = "https://github.com/nullslate/app-template.git";
-const DEFAULT_LIB_TEMPLATE_REPO: &str = "https://github.com/nullslate/lib-template.git";
-const DEFAULT_FULLSTACK_TEMPLATE_REPO: &str = "https://github.com/nullslate/app-template.git";
-
-pub fn default_template_url(project_type: &str) -> &'static str {
-    match project_type {
-        "lib" => DEFAULT_LIB_TEMPLATE_REPO,
-        "fullstack" => DEFAULT_FULLSTACK_TEMPLATE_REPO,
-        _ => DEFAULT_TEMPLATE_REPO,
-    }
-}
-
 #[derive(Parser, Debug)]
 #[command(name = "nullslate")]
 #[command(about = "CLI for the nullslate dev tooling ecosystem", long_about = None)]
 pub struct Cli {
+    /// Output format: human-readable or one JSON event per line
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Human)]
+    pub output: OutputFormat,
+
+    /// Shorthand for --output jsonl
+    #[arg(long, global = true)]
+    pub json: bool,
+
     #[command(subcommand)]
     pub command: Commands,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Human,
+    Jsonl,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum PmArg {
+    Bun,
+    Npm,
+    Pnpm,
+    Yarn,
+}
+
+impl From<PmArg> for PackageManager {
+    fn from(arg: PmArg) -> Self {
+        match arg {
+            PmArg::Bun => PackageManager::Bun,
+            PmArg::Npm => PackageManager::Npm,
+            PmArg::Pnpm => PackageManager::Pnpm,
+            PmArg::Yarn => PackageManager::Yarn,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum AuthProviderArg {
+    Github,
+    Google,
+    Discord,
+    /// Magic link sent by email
+    Email,
+    /// Username and password
+    Credentials,
+}
+
+impl From<AuthProviderArg> for AuthProvider {
+    fn from(arg: AuthProviderArg) -> Self {
+        match arg {
+            AuthProviderArg::Github => AuthProvider::GitHub,
+            AuthProviderArg::Google => AuthProvider::Google,
+            AuthProviderArg::Discord => AuthProvider::Discord,
+            AuthProviderArg::Email => AuthProvider::Email,
+            AuthProviderArg::Credentials => AuthProvider::Credentials,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum DbArg {
+    Postgres,
+    Sqlite,
+    Mysql,
+    None,
+}
+
+impl From<DbArg> for Database {
+    fn from(arg: DbArg) -> Self {
+        match arg {
+            DbArg::Postgres => Database::Postgres,
+            DbArg::Sqlite => Database::Sqlite,
+            DbArg::Mysql => Database::Mysql,
+            DbArg::None => Database::None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OrmArg {
+    /// The database driver on its own
+    Raw,
+    Drizzle,
+    Kysely,
+}
+
+impl From<OrmArg> for Orm {
+    fn from(arg: OrmArg) -> Self {
+        match arg {
+            OrmArg::Raw => Orm::Raw,
+            OrmArg::Drizzle => Orm::Drizzle,
+            OrmArg::Kysely => Orm::Kysely,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum LangArg {
+    #[value(alias = "ts")]
+    Typescript,
+    #[value(alias = "js")]
+    Javascript,
+}
+
+impl From<LangArg> for Language {
+    fn from(arg: LangArg) -> Self {
+        match arg {
+            LangArg::Typescript => Language::TypeScript,
+            LangArg::Javascript => Language::JavaScript,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum VersionsArg {
+    /// Template lockfile if present, else template.json pins, else package.json ranges
+    Auto,
+    /// Install exactly what the template's lockfile records
+    Lockfile,
+    /// Pin managed packages to the versions in template.json
+    Pinned,
+    /// Upgrade managed packages to their latest release
+    Latest,
+}
+
+impl From<VersionsArg> for VersionPolicy {
+    fn from(arg: VersionsArg) -> Self {
+        match arg {
+            VersionsArg::Auto => VersionPolicy::Auto,
+            VersionsArg::Lockfile => VersionPolicy::Lockfile,
+            VersionsArg::Pinned => VersionPolicy::Pinned,
+            VersionsArg::Latest => VersionPolicy::Latest,
+        }
+    }
+}
+
 #[derive(Subcommand, Debug)]
 pub enum Commands {
     /// Scaffold a new project
-    Init(InitArgs),
+    Init(Box<InitArgs>),
     /// Start the dev environment (auto-detects project type)
-    Dev,
+    Dev {
+        /// Env files to load: .env.<mode> and .env.<mode>.local [default: development]
+        #[arg(long)]
+        mode: Option<String>,
+        /// Settings from [profiles.<name>] in nullslate.toml
+        #[arg(long)]
+        profile: Option<String>,
+        /// Cargo target triple, over the profile's
+        #[arg(long)]
+        target: Option<String>,
+        /// Passed on to the dev server, after `--`, e.g. `ns dev -- --port 3001`
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
     /// Build the project (auto-detects project type)
-    Build,
+    Build {
+        /// Env files to load: .env.<mode> and .env.<mode>.local [default: production]
+        #[arg(long)]
+        mode: Option<String>,
+        /// Build even when the cache has a build of the same inputs
+        #[arg(long)]
+        no_cache: bool,
+        /// How many builds may run at once [default: all]
+        #[arg(long, short, value_parser = clap::value_parser!(u16).range(1..))]
+        jobs: Option<u16>,
+        /// Settings from [profiles.<name>] in nullslate.toml
+        #[arg(long)]
+        profile: Option<String>,
+        /// Cargo target triple, over the profile's
+        #[arg(long)]
+        target: Option<String>,
+        /// Passed on to cargo, or to the web build in frontend projects, after `--`
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
+    /// Run a task from nullslate.toml, after the tasks it depends on
+    Run {
+        /// Task to run; lists the tasks when omitted
+        task: Option<String>,
+        /// Env files to load: .env.<mode> and .env.<mode>.local [default: production for build, else development]
+        #[arg(long)]
+        mode: Option<String>,
+        /// Run tasks even when the cache has a run with the same inputs
+        #[arg(long)]
+        no_cache: bool,
+        /// How many tasks may run at once [default: all]
+        #[arg(long, short, value_parser = clap::value_parser!(u16).range(1..))]
+        jobs: Option<u16>,
+        /// Settings from [profiles.<name>] in nullslate.toml
+        #[arg(long)]
+        profile: Option<String>,
+    },
+    /// Check, sync, rotate, encrypt and show the project's .env files
+    Env {
+        #[command(subcommand)]
+        command: EnvCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum EnvCommand {
+    /// Report keys from .env.example that .env is missing or leaves empty
+    Check,
+    /// Append keys from .env.example that .env is missing
+    Sync,
+    /// Replace a key's value with a new random secret
+    Rotate {
+        /// Key to rotate, e.g. AUTH_SECRET
+        key: String,
+    },
+    /// Encrypt .env into .env.enc, which is safe to commit
+    Encrypt,
+    /// Decrypt .env.enc back into .env
+    Decrypt {
+        /// Overwrite a .env that has local changes
+        #[arg(long)]
+        force: bool,
+    },
+    /// Print the variables `ns dev` would load, with their source, secrets masked
+    Show {
+        /// Env files to load: .env.<mode> and .env.<mode>.local
+        #[arg(long, default_value = "development")]
+        mode: String,
+    },
 }
 
 #[derive(Parser, Debug)]
@@ -45,23 +248,75 @@ pub struct InitArgs {
     #[arg(long)]
     pub no_auth: bool,
 
-    /// Database type: postgres or none
-    #[arg(long, default_value = "none")]
-    pub db: String,
+    /// Auth.js providers, comma-separated (default: github)
+    #[arg(long, value_enum, value_delimiter = ',', conflicts_with = "no_auth")]
+    pub auth_providers: Vec<AuthProviderArg>,
+
+    /// Database to set up
+    #[arg(long, value_enum, default_value_t = DbArg::None)]
+    pub db: DbArg,
+
+    /// ORM or query builder for the database (app only)
+    #[arg(long, value_enum, default_value_t = OrmArg::Raw)]
+    pub orm: OrmArg,
 
     /// Output directory (default: ./<project-name>)
     #[arg(long)]
     pub path: Option<PathBuf>,
 
+    /// Scaffold into an existing directory, keeping files that already exist
+    #[arg(long, conflicts_with = "force")]
+    pub merge: bool,
+
+    /// Scaffold into an existing directory, overwriting files (originals are backed up)
+    #[arg(long)]
+    pub force: bool,
+
+    /// Render the project in memory and list the files it would write
+    #[arg(long)]
+    pub dry_run: bool,
+
     /// Skip git initialization
     #[arg(long)]
     pub no_git: bool,
 
-    /// Skip npm install
+    /// Initial git branch name
+    #[arg(long, conflicts_with = "no_git")]
+    pub branch: Option<String>,
+
+    /// Author of the initial commit, as "Name <email>"
+    #[arg(long, conflicts_with = "no_git")]
+    pub author: Option<String>,
+
+    /// Message for the initial commit
+    #[arg(long, conflicts_with = "no_git")]
+    pub message: Option<String>,
+
+    /// Initialize the repository without committing
+    #[arg(long, conflicts_with = "no_git")]
+    pub no_commit: bool,
+
+    /// Add this URL as the `origin` remote
+    #[arg(long, conflicts_with = "no_git")]
+    pub remote: Option<String>,
+
+    /// Package manager used to install and run the project
+    #[arg(long, value_enum, default_value_t = PmArg::Bun)]
+    pub pm: PmArg,
+
+    /// Skip dependency install
     #[arg(long)]
     pub no_install: bool,
 
-    /// Custom template repository URL
+    /// How dependency versions are chosen
+    #[arg(long, value_enum, default_value_t = VersionsArg::Auto)]
+    pub versions: VersionsArg,
+
+    /// Shorthand for --versions latest
+    #[arg(long, conflicts_with = "versions")]
+    pub latest: bool,
+
+    /// Custom template repository URL or local template directory
     #[arg(long)]
     pub template: Option<String>,
 
@@ -77,9 +332,9 @@ pub struct InitArgs {
     #[arg(long)]
     pub fullstack: bool,
 
-    /// Language: typescript or javascript (lib only)
-    #[arg(long, default_value = "typescript")]
-    pub lang: String,
+    /// Language of the generated code
+    #[arg(long, value_enum, default_value_t = LangArg::Typescript)]
+    pub lang: LangArg,
 
     /// Include React support (lib only)
     #[arg(long)]
@@ -97,3 +352,31 @@ pub struct InitArgs {
     #[arg(short, long)]
     pub yes: bool,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passthrough_args_follow_a_double_dash() {
+        let cli = Cli::try_parse_from(["ns", "build", "--", "--locked", "--no-cache"]).unwrap();
+        let Commands::Build { no_cache, args, .. } = cli.command else { panic!() };
+        assert!(!no_cache);
+        assert_eq!(args, ["--locked", "--no-cache"]);
+
+        // Without `--` every flag is nullslate's, and unknown ones are errors
+        let cli = Cli::try_parse_from(["ns", "build", "--target", "x86_64-unknown-linux-musl"]).unwrap();
+        let Commands::Build { target, args, .. } = cli.command else { panic!() };
+        assert_eq!(target.as_deref(), Some("x86_64-unknown-linux-musl"));
+        assert!(args.is_empty());
+        assert!(Cli::try_parse_from(["ns", "build", "--locked"]).is_err());
+    }
+
+    #[test]
+    fn lang_is_checked() {
+        let cli = Cli::try_parse_from(["ns", "init", "--lang", "js"]).unwrap();
+        let Commands::Init(args) = cli.command else { panic!() };
+        assert_eq!(args.lang, LangArg::Javascript);
+        assert!(Cli::try_parse_from(["ns", "init", "--lang", "JavaScript"]).is_err());
+    }
+}
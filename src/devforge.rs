@@ -0,0 +1,331 @@
+use anyhow::{Context, Result};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use toml_edit::{DocumentMut, Item, Table, Value};
+
+pub const DEVFORGE_FILE: &str = "devforge.toml";
+
+/// How long a service may take to pass its readiness check by default.
+const DEFAULT_READY_TIMEOUT: Duration = Duration::from_secs(60);
+
+const TOP_LEVEL_KEYS: [&str; 2] = ["database", "services"];
+const DATABASE_KEYS: [&str; 3] = ["kind", "url_env", "migrations"];
+const SERVICE_KEYS: [&str; 6] = ["command", "cwd", "env", "ports", "depends_on", "ready"];
+const READY_KEYS: [&str; 4] = ["tcp", "http", "path", "timeout"];
+
+/// The dev orchestration config of a fullstack project, `devforge.toml`.
+#[derive(Debug, Default, PartialEq)]
+pub struct Devforge {
+    /// Services in the order they should start: dependencies first, then
+    /// declaration order.
+    pub services: Vec<Service>,
+    /// Keys nullslate doesn't know, e.g. `services.api.depend_on`.
+    pub unknown_keys: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Service {
+    pub name: String,
+    /// Run through the shell, so `&&` and quoting work.
+    pub command: String,
+    /// Relative to the project root.
+    pub cwd: Option<PathBuf>,
+    pub env: Vec<(String, String)>,
+    pub ports: Vec<u16>,
+    pub depends_on: Vec<String>,
+    pub ready: Option<Readiness>,
+}
+
+/// When a service counts as up, so the ones depending on it can start.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Readiness {
+    pub probe: Probe,
+    pub timeout: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Probe {
+    /// Something accepts connections on the localhost port.
+    Tcp(u16),
+    /// A GET of `path` on the localhost port answers 200.
+    Http { port: u16, path: String },
+}
+
+impl fmt::Display for Probe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Probe::Tcp(port) => write!(f, "nothing listening on localhost:{port}"),
+            Probe::Http { port, path } => write!(f, "GET http://localhost:{port}{path} didn't return 200"),
+        }
+    }
+}
+
+impl Devforge {
+    /// Read `devforge.toml` from `root`, if there is one.
+    pub fn load(root: &Path) -> Result<Option<Devforge>> {
+        let path = root.join(DEVFORGE_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(&path)?;
+        Devforge::parse(&content)
+            .with_context(|| format!("Invalid {}", path.display()))
+            .map(Some)
+    }
+
+    pub fn parse(content: &str) -> Result<Devforge> {
+        let doc: DocumentMut = content.parse()?;
+        let mut config = Devforge::default();
+        unknown_keys(doc.as_table(), "", &TOP_LEVEL_KEYS, &mut config.unknown_keys);
+        if let Some(database) = doc.get("database").and_then(Item::as_table_like) {
+            for (key, _) in database.iter() {
+                if !DATABASE_KEYS.contains(&key) {
+                    config.unknown_keys.push(format!("database.{key}"));
+                }
+            }
+        }
+
+        let Some(services) = doc.get("services") else {
+            return Ok(config);
+        };
+        let services = services
+            .as_table()
+            .ok_or_else(|| anyhow::anyhow!("services must be a table of [services.<name>] sections"))?;
+        let mut declared = Vec::new();
+        for (name, item) in services.iter() {
+            let table = item
+                .as_table()
+                .ok_or_else(|| anyhow::anyhow!("services.{name} must be a table"))?;
+            declared.push(parse_service(name, table, &mut config.unknown_keys)?);
+        }
+        config.services = start_order(declared)?;
+        Ok(config)
+    }
+}
+
+fn unknown_keys(table: &Table, prefix: &str, known: &[&str], out: &mut Vec<String>) {
+    for (key, _) in table.iter() {
+        if !known.contains(&key) {
+            out.push(format!("{prefix}{key}"));
+        }
+    }
+}
+
+fn parse_service(name: &str, table: &Table, unknown: &mut Vec<String>) -> Result<Service> {
+    let prefix = format!("services.{name}.");
+    unknown_keys(table, &prefix, &SERVICE_KEYS, unknown);
+    let field = |key: &str| table.get(key).and_then(Item::as_value);
+    let invalid = |key: &str, expected: &str| anyhow::anyhow!("{prefix}{key} must be {expected}");
+
+    let command = field("command")
+        .ok_or_else(|| anyhow::anyhow!("services.{name} has no command"))?
+        .as_str()
+        .ok_or_else(|| invalid("command", "a string"))?
+        .to_string();
+    let cwd = match field("cwd") {
+        Some(cwd) => Some(PathBuf::from(cwd.as_str().ok_or_else(|| invalid("cwd", "a string"))?)),
+        None => None,
+    };
+
+    let mut env = Vec::new();
+    if let Some(item) = table.get("env") {
+        let vars = item
+            .as_table_like()
+            .ok_or_else(|| invalid("env", "a table of strings"))?;
+        for (key, value) in vars.iter() {
+            let value = value
+                .as_str()
+                .ok_or_else(|| invalid(&format!("env.{key}"), "a string"))?;
+            env.push((key.to_string(), value.to_string()));
+        }
+    }
+
+    let ports = match field("ports") {
+        Some(ports) => ports
+            .as_array()
+            .ok_or_else(|| invalid("ports", "an array of port numbers"))?
+            .iter()
+            .map(|port| as_port(port).ok_or_else(|| invalid("ports", "an array of port numbers")))
+            .collect::<Result<Vec<u16>>>()?,
+        None => Vec::new(),
+    };
+    let depends_on = match field("depends_on") {
+        Some(deps) => deps
+            .as_array()
+            .ok_or_else(|| invalid("depends_on", "an array of service names"))?
+            .iter()
+            .map(|dep| {
+                dep.as_str()
+                    .map(str::to_string)
+                    .ok_or_else(|| invalid("depends_on", "an array of service names"))
+            })
+            .collect::<Result<Vec<String>>>()?,
+        None => Vec::new(),
+    };
+
+    let ready = match table.get("ready") {
+        Some(item) => {
+            let ready = item
+                .as_table_like()
+                .ok_or_else(|| invalid("ready", "a table like { tcp = 5432 } or { http = 3000 }"))?;
+            for (key, _) in ready.iter() {
+                if !READY_KEYS.contains(&key) {
+                    unknown.push(format!("{prefix}ready.{key}"));
+                }
+            }
+            let port = |key: &str| match ready.get(key).and_then(Item::as_value) {
+                Some(value) => as_port(value)
+                    .map(Some)
+                    .ok_or_else(|| invalid(&format!("ready.{key}"), "a port number")),
+                None => Ok(None),
+            };
+            let probe = match (port("tcp")?, port("http")?) {
+                (Some(port), None) => Probe::Tcp(port),
+                (None, Some(port)) => {
+                    let path = match ready.get("path").and_then(Item::as_value) {
+                        Some(path) => path
+                            .as_str()
+                            .ok_or_else(|| invalid("ready.path", "a string"))?
+                            .to_string(),
+                        None => "/".to_string(),
+                    };
+                    Probe::Http { port, path }
+                }
+                _ => return Err(invalid("ready", "either { tcp = <port> } or { http = <port> }")),
+            };
+            let timeout = match ready.get("timeout").and_then(Item::as_value) {
+                Some(secs) => Duration::from_secs(
+                    secs.as_integer()
+                        .and_then(|secs| u64::try_from(secs).ok())
+                        .ok_or_else(|| invalid("ready.timeout", "a number of seconds"))?,
+                ),
+                None => DEFAULT_READY_TIMEOUT,
+            };
+            Some(Readiness { probe, timeout })
+        }
+        None => None,
+    };
+
+    Ok(Service {
+        name: name.to_string(),
+        command,
+        cwd,
+        env,
+        ports,
+        depends_on,
+        ready,
+    })
+}
+
+fn as_port(value: &Value) -> Option<u16> {
+    value
+        .as_integer()
+        .and_then(|port| u16::try_from(port).ok())
+        .filter(|port| *port != 0)
+}
+
+/// Order services so each comes after what it depends on, keeping the
+/// declaration order otherwise.
+fn start_order(declared: Vec<Service>) -> Result<Vec<Service>> {
+    for service in &declared {
+        for dep in &service.depends_on {
+            if !declared.iter().any(|s| s.name == *dep) {
+                anyhow::bail!("services.{} depends on unknown service {dep:?}", service.name);
+            }
+        }
+    }
+
+    let mut ordered: Vec<Service> = Vec::new();
+    let mut pending = declared;
+    while !pending.is_empty() {
+        let next = pending.iter().position(|service| {
+            service
+                .depends_on
+                .iter()
+                .all(|dep| ordered.iter().any(|s| s.name == *dep))
+        });
+        match next {
+            Some(i) => ordered.push(pending.remove(i)),
+            None => {
+                let names: Vec<&str> = pending.iter().map(|s| s.name.as_str()).collect();
+                anyhow::bail!("Services depend on each other in a cycle: {}", names.join(", "));
+            }
+        }
+    }
+    Ok(ordered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+[database]
+kind = "postgres"
+
+[services.web]
+command = "bun run dev"
+cwd = "web"
+depends_on = ["api"]
+ports = [3000]
+ready = { http = 3000 }
+
+[services.api]
+command = "cargo run"
+cwd = "api"
+env = { RUST_LOG = "info" }
+depends_on = ["db"]
+ready = { http = 3001, path = "/health", timeout = 120 }
+restart = true
+
+[services.db]
+command = "docker compose up postgres"
+ports = [5432]
+ready = { tcp = 5432 }
+"#;
+
+    #[test]
+    fn parses_services_in_dependency_order() {
+        let config = Devforge::parse(CONFIG).unwrap();
+        let names: Vec<&str> = config.services.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["db", "api", "web"]);
+
+        let api = &config.services[1];
+        assert_eq!(api.cwd.as_deref(), Some(Path::new("api")));
+        assert_eq!(api.env, [("RUST_LOG".to_string(), "info".to_string())]);
+        assert_eq!(
+            api.ready,
+            Some(Readiness {
+                probe: Probe::Http { port: 3001, path: "/health".to_string() },
+                timeout: Duration::from_secs(120),
+            })
+        );
+        assert_eq!(config.services[0].ready.as_ref().unwrap().probe, Probe::Tcp(5432));
+        assert_eq!(config.services[2].ports, [3000]);
+    }
+
+    #[test]
+    fn reports_unknown_keys() {
+        let config = Devforge::parse(&format!("{CONFIG}\n[web]\ncommand = \"x\"\n")).unwrap();
+        assert_eq!(config.unknown_keys, ["web", "services.api.restart"]);
+    }
+
+    #[test]
+    fn rejects_bad_dependencies_and_values() {
+        let cycle = "[services.a]\ncommand = \"a\"\ndepends_on = [\"b\"]\n\
+                     [services.b]\ncommand = \"b\"\ndepends_on = [\"a\"]\n";
+        let err = Devforge::parse(cycle).unwrap_err().to_string();
+        assert!(err.contains("cycle: a, b"), "{err}");
+
+        let missing = "[services.a]\ncommand = \"a\"\ndepends_on = [\"db\"]\n";
+        assert!(Devforge::parse(missing).unwrap_err().to_string().contains("unknown service \"db\""));
+
+        let port = "[services.a]\ncommand = \"a\"\nports = [70000]\n";
+        assert!(Devforge::parse(port).unwrap_err().to_string().contains("ports must be"));
+
+        let both = "[services.a]\ncommand = \"a\"\nready = { tcp = 1, http = 2 }\n";
+        assert!(Devforge::parse(both).is_err());
+        assert!(Devforge::parse("[services.a]\ncwd = \"x\"\n").is_err());
+    }
+}
@@ -0,0 +1,230 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+/// Bumped whenever an existing event changes shape. Adding new events or
+/// new optional fields does not bump it.
+pub const SCHEMA_VERSION: u32 = 1;
+
+static JSONL: AtomicBool = AtomicBool::new(false);
+static HUMAN: OnceLock<fn(&Event)> = OnceLock::new();
+
+/// One step of `ns init`, `ns dev` or `ns build`, as seen by tooling.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    StepStarted { step: Step },
+    StepFinished { step: Step },
+    StepFailed { step: Step, message: String },
+    TemplateFetched { url: String },
+    FileCopied { path: PathBuf },
+    FileSkipped { path: PathBuf },
+    FileBackedUp { path: PathBuf },
+    PackageJsonEdited { path: PathBuf },
+    FileEdited { path: PathBuf },
+    GitCommitted { path: PathBuf },
+    GitSkipped { reason: String },
+    InstallFinished { path: PathBuf },
+    VersionsResolved { policy: String, packages: BTreeMap<String, String> },
+    ProcessStarted { program: String, args: Vec<String>, cwd: PathBuf },
+    ProcessOutput { program: String, stream: Stream, line: String },
+    ProcessExited { program: String, code: Option<i32> },
+    ServiceReady { service: String, elapsed_ms: u64 },
+    /// Every service has started (or the first one exited while starting).
+    ServicesStarted { services: Vec<ServiceStatus> },
+    /// A service exited, so the others still running are being stopped.
+    ServicesStopping { exited: String, stopping: Vec<String> },
+    CacheHit { task: String, restored: bool },
+    CacheMiss { task: String },
+    TaskFinished { task: String, elapsed_ms: u64 },
+    /// The end of a task run: each task that runs commands or is cached,
+    /// and the cache hits and misses.
+    TasksSummary {
+        tasks: Vec<TaskTiming>,
+        total_ms: u64,
+        cache_hits: Vec<String>,
+        cache_misses: usize,
+    },
+    /// A task as `ns run` lists it; built-ins have no commands.
+    TaskListed { task: String, commands: Vec<String>, depends_on: Vec<String>, builtin: bool },
+    /// A variable as `ns env show` lists it, masked the same way.
+    EnvVar { dir: PathBuf, key: String, value: String, source: String },
+    Finished { project: String, path: PathBuf },
+    Warning { message: String },
+    Error { message: String },
+}
+
+/// A service in `services_started`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ServiceStatus {
+    pub service: String,
+    pub ports: Vec<u16>,
+    /// How long its readiness check took to pass, if it has one.
+    pub ready_ms: Option<u64>,
+    /// Whether it already exited, and with what code.
+    pub exited: bool,
+    pub code: Option<i32>,
+}
+
+/// A task in `tasks_summary`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TaskTiming {
+    pub task: String,
+    pub outcome: TaskOutcome,
+    /// Time spent running; none for cached or unstarted tasks.
+    pub elapsed_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskOutcome {
+    Finished,
+    Cached,
+    Failed,
+    Cancelled,
+    NotStarted,
+}
+
+/// A callback receiving events as they happen.
+pub(crate) type ProgressFn<'a> = Box<dyn FnMut(&Event) + 'a>;
+
+/// Long-running scaffold phases, bracketed by `step_started`/`step_finished`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Step {
+    FetchTemplate,
+    ProcessFiles,
+    GitInit,
+    Install,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Serialize)]
+struct Envelope<'a> {
+    schema: u32,
+    #[serde(flatten)]
+    event: &'a Event,
+}
+
+pub fn set_jsonl(enabled: bool) {
+    JSONL.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_jsonl() -> bool {
+    JSONL.load(Ordering::Relaxed)
+}
+
+pub fn to_json_line(event: &Event) -> String {
+    let envelope = Envelope {
+        schema: SCHEMA_VERSION,
+        event,
+    };
+    // Serializing plain strings, paths and integers cannot fail
+    serde_json::to_string(&envelope).expect("event is serializable")
+}
+
+/// Show events in human mode with `handler`, e.g. to print summaries as
+/// text. Only the first handler set is used.
+pub fn set_human_handler(handler: fn(&Event)) {
+    let _ = HUMAN.set(handler);
+}
+
+/// Write the event to stdout as a single JSON line, or in human mode hand
+/// it to the handler, if one is set.
+pub fn emit(event: Event) {
+    if !is_jsonl() {
+        if let Some(handler) = HUMAN.get() {
+            handler(&event);
+        }
+        return;
+    }
+    let mut stdout = std::io::stdout().lock();
+    let _ = writeln!(stdout, "{}", to_json_line(&event));
+    let _ = stdout.flush();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_json_has_schema_and_tag() {
+        let line = to_json_line(&Event::TemplateFetched {
+            url: "https://example.com/t.git".to_string(),
+        });
+        assert_eq!(
+            line,
+            r#"{"schema":1,"event":"template_fetched","url":"https://example.com/t.git"}"#
+        );
+    }
+
+    #[test]
+    fn step_events_name_the_step() {
+        let line = to_json_line(&Event::StepStarted {
+            step: Step::FetchTemplate,
+        });
+        assert_eq!(
+            line,
+            r#"{"schema":1,"event":"step_started","step":"fetch_template"}"#
+        );
+    }
+
+    #[test]
+    fn process_exited_serializes_missing_code_as_null() {
+        let line = to_json_line(&Event::ProcessExited {
+            program: "bun".to_string(),
+            code: None,
+        });
+        assert_eq!(
+            line,
+            r#"{"schema":1,"event":"process_exited","program":"bun","code":null}"#
+        );
+    }
+
+    #[test]
+    fn process_output_stream_is_snake_case() {
+        let line = to_json_line(&Event::ProcessOutput {
+            program: "cargo".to_string(),
+            stream: Stream::Stderr,
+            line: "Compiling".to_string(),
+        });
+        assert!(line.contains(r#""stream":"stderr""#));
+    }
+
+    #[test]
+    fn task_listed_marks_builtins() {
+        let line = to_json_line(&Event::TaskListed {
+            task: "build".to_string(),
+            commands: Vec::new(),
+            depends_on: Vec::new(),
+            builtin: true,
+        });
+        assert_eq!(
+            line,
+            r#"{"schema":1,"event":"task_listed","task":"build","commands":[],"depends_on":[],"builtin":true}"#
+        );
+    }
+
+    #[test]
+    fn env_var_names_its_dir_and_source() {
+        let line = to_json_line(&Event::EnvVar {
+            dir: PathBuf::from("web"),
+            key: "AUTH_SECRET".to_string(),
+            value: "********".to_string(),
+            source: ".env".to_string(),
+        });
+        assert_eq!(
+            line,
+            r#"{"schema":1,"event":"env_var","dir":"web","key":"AUTH_SECRET","value":"********","source":".env"}"#
+        );
+    }
+}
@@ -1,9 +1,21 @@
 use anyhow::Result;
 use rand::RngExt;
-use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-pub fn get_files_to_skip(include_docs: bool, include_auth: bool, include_db: bool) -> Vec<&'static str> {
+use crate::auth::{env_section, AuthProvider, PROVIDERS_DIR};
+use crate::database::Database;
+use crate::orm::Orm;
+use crate::template::OutputDir;
+use crate::vfs::FileSystem;
+
+/// Files for features that are turned off. Source files are named by their
+/// TypeScript side only; `select_variants` skips the JavaScript variants.
+pub fn get_files_to_skip(
+    include_docs: bool,
+    include_auth: bool,
+    include_db: bool,
+    orm: Orm,
+) -> Vec<&'static str> {
     let mut skip = Vec::new();
 
     if !include_docs {
@@ -20,34 +32,52 @@ pub fn get_files_to_skip(include_docs: bool, include_auth: bool, include_db: boo
         skip.push("src/lib/auth.ts");
         skip.push("api/auth");
         skip.push("src/components/session-provider.tsx");
+        skip.push(PROVIDERS_DIR);
     }
 
     if !include_db {
         skip.push("src/lib/db.ts");
     }
 
+    // Files for the ORMs that weren't picked (all of them without a db)
+    let orm = if include_db { orm } else { Orm::Raw };
+    for other in Orm::ALL.iter().filter(|other| **other != orm) {
+        skip.extend(other.files());
+    }
+
     skip
 }
 
+/// A pattern matches the path itself, anything below it and its flat-route
+/// siblings: `src/routes/docs` covers `src/routes/docs.tsx` and
+/// `src/routes/docs.$slug.tsx`, while `src/lib/db.ts` leaves
+/// `src/lib/db.tsx` alone.
 pub fn should_skip_file(path: &str, skip_patterns: &[&str]) -> bool {
-    for pattern in skip_patterns {
-        if path.starts_with(pattern) || path == *pattern {
-            return true;
-        }
-    }
-    false
+    skip_patterns.iter().any(|pattern| {
+        path.strip_prefix(pattern)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(['/', '\\', '.']))
+    })
 }
 
 pub fn update_package_json(
+    fs: &mut dyn FileSystem,
     output_path: &Path,
+    lang: &str,
     include_docs: bool,
     include_auth: bool,
-    include_db: bool,
+    db: Database,
+    orm: Orm,
 ) -> Result<()> {
     let package_json_path = output_path.join("package.json");
-    let content = fs::read_to_string(&package_json_path)?;
+    let content = fs.read_to_string(&package_json_path)?;
     let mut package: serde_json::Value = serde_json::from_str(&content)?;
 
+    if lang == "javascript" {
+        if let Some(dev_deps) = package["devDependencies"].as_object_mut() {
+            dev_deps.remove("typescript");
+        }
+    }
+
     // Remove deps when features are OFF (template ships with all deps)
     if !include_docs {
         if let Some(deps) = package["dependencies"].as_object_mut() {
@@ -67,27 +97,112 @@ pub fn update_package_json(
         }
     }
 
-    if !include_db {
-        if let Some(deps) = package["dependencies"].as_object_mut() {
-            deps.remove("pg");
+    // Keep only the chosen database's driver
+    for other in Database::ENABLED.iter().filter(|other| **other != db) {
+        remove_packages(&mut package, other.npm_packages());
+    }
+    // Same for the ORM and its scripts
+    let orm = if db.is_enabled() { orm } else { Orm::Raw };
+    for other in Orm::ALL.iter().filter(|other| **other != orm) {
+        remove_packages(&mut package, other.npm_packages());
+        if let Some(scripts) = package["scripts"].as_object_mut() {
+            for (name, command) in other.scripts() {
+                if scripts.get(*name).and_then(|v| v.as_str()) == Some(*command) {
+                    scripts.remove(*name);
+                }
+            }
         }
-        if let Some(dev_deps) = package["devDependencies"].as_object_mut() {
-            dev_deps.remove("@types/pg");
+    }
+    if !orm.scripts().is_empty() && !package["scripts"].is_object() {
+        package["scripts"] = serde_json::json!({});
+    }
+    if let Some(scripts) = package["scripts"].as_object_mut() {
+        for (name, command) in orm.scripts() {
+            scripts.insert(name.to_string(), command.to_string().into());
         }
     }
 
+    add_packages(&mut package, db.npm_packages());
+    add_packages(&mut package, orm.npm_packages());
+
     let formatted = serde_json::to_string_pretty(&package)?;
-    fs::write(&package_json_path, formatted)?;
+    fs.write(&package_json_path, formatted.as_bytes())?;
 
     Ok(())
 }
 
-pub fn cleanup_layout_for_no_auth(output_path: &Path) -> Result<()> {
-    let layout_path = output_path.join("src/routes/__root.tsx");
-    if !layout_path.exists() {
-        return Ok(());
+/// Keep the packages of the picked auth providers and drop the others'.
+pub fn update_auth_packages(
+    fs: &mut dyn FileSystem,
+    output_path: &Path,
+    providers: &[AuthProvider],
+) -> Result<()> {
+    let package_json_path = output_path.join("package.json");
+    let content = fs.read_to_string(&package_json_path)?;
+    let mut package: serde_json::Value = serde_json::from_str(&content)?;
+
+    for provider in AuthProvider::ALL {
+        if providers.contains(&provider) {
+            add_packages(&mut package, provider.npm_packages());
+        } else {
+            remove_packages(&mut package, provider.npm_packages());
+        }
     }
-    let content = fs::read_to_string(&layout_path)?;
+
+    let formatted = serde_json::to_string_pretty(&package)?;
+    fs.write(&package_json_path, formatted.as_bytes())?;
+    Ok(())
+}
+
+/// `(name, version, dev)` entries as returned by `npm_packages()`.
+type NpmPackages = [(&'static str, &'static str, bool)];
+
+fn section(dev: bool) -> &'static str {
+    if dev {
+        "devDependencies"
+    } else {
+        "dependencies"
+    }
+}
+
+fn remove_packages(package: &mut serde_json::Value, packages: &NpmPackages) {
+    for (name, _, dev) in packages {
+        if let Some(deps) = package[section(*dev)].as_object_mut() {
+            deps.remove(*name);
+        }
+    }
+}
+
+/// Add packages that aren't there yet, keeping any version the template has.
+fn add_packages(package: &mut serde_json::Value, packages: &NpmPackages) {
+    for (name, version, dev) in packages {
+        let deps = &mut package[section(*dev)];
+        if !deps.is_object() {
+            *deps = serde_json::json!({});
+        }
+        if let Some(deps) = deps.as_object_mut() {
+            deps.entry(*name).or_insert_with(|| version.to_string().into());
+        }
+    }
+}
+
+/// Root layout in either language.
+pub const ROOT_LAYOUTS: &[&str] = &["src/routes/__root.tsx", "src/routes/__root.jsx"];
+
+/// Strip the session provider from the root layout. Returns the edited
+/// file, if there was one.
+pub fn cleanup_layout_for_no_auth(
+    fs: &mut dyn FileSystem,
+    output_path: &Path,
+) -> Result<Option<PathBuf>> {
+    let Some(layout_path) = ROOT_LAYOUTS
+        .iter()
+        .map(|layout| output_path.join(layout))
+        .find(|path| fs.exists(path))
+    else {
+        return Ok(None);
+    };
+    let content = fs.read_to_string(&layout_path)?;
 
     // Remove SessionProvider import (no semicolons in new template)
     let content = content.replace(
@@ -99,8 +214,8 @@ pub fn cleanup_layout_for_no_auth(output_path: &Path) -> Result<()> {
     let content = content.replace("        <SessionProvider>\n", "");
     let content = content.replace("        </SessionProvider>\n", "");
 
-    fs::write(&layout_path, content)?;
-    Ok(())
+    fs.write(&layout_path, content.as_bytes())?;
+    Ok(Some(layout_path))
 }
 
 pub fn get_lib_files_to_skip(
@@ -131,7 +246,22 @@ pub fn get_lib_files_to_skip(
     skip
 }
 
+/// Set package.json's `name`, which templates fill with `{{project_name}}`,
+/// to the full package name including any scope.
+pub fn set_package_name(fs: &mut dyn FileSystem, output_path: &Path, name: &str) -> Result<()> {
+    let package_json_path = output_path.join("package.json");
+    let content = fs.read_to_string(&package_json_path)?;
+    let mut package: serde_json::Value = serde_json::from_str(&content)?;
+    if package.get("name").is_some_and(|current| current != name) {
+        package["name"] = name.into();
+        let formatted = serde_json::to_string_pretty(&package)?;
+        fs.write(&package_json_path, formatted.as_bytes())?;
+    }
+    Ok(())
+}
+
 pub fn update_lib_package_json(
+    fs: &mut dyn FileSystem,
     output_path: &Path,
     lang: &str,
     include_react: bool,
@@ -139,7 +269,7 @@ pub fn update_lib_package_json(
     include_testing: bool,
 ) -> Result<()> {
     let package_json_path = output_path.join("package.json");
-    let content = fs::read_to_string(&package_json_path)?;
+    let content = fs.read_to_string(&package_json_path)?;
     let mut package: serde_json::Value = serde_json::from_str(&content)?;
 
     if lang == "javascript" {
@@ -185,7 +315,7 @@ pub fn update_lib_package_json(
     }
 
     let formatted = serde_json::to_string_pretty(&package)?;
-    fs::write(&package_json_path, formatted)?;
+    fs.write(&package_json_path, formatted.as_bytes())?;
 
     Ok(())
 }
@@ -195,16 +325,20 @@ pub fn generate_random_secret() -> String {
     bytes.iter().map(|b| format!("{:02x}", b)).collect()
 }
 
-pub fn generate_env_file(output_path: &Path) -> Result<()> {
+pub fn generate_env_file(
+    output_path: &Path,
+    output: &mut OutputDir,
+    providers: &[AuthProvider],
+) -> Result<()> {
     let env_path = output_path.join(".env");
+    if !output.prepare_write(&env_path)? {
+        return Ok(());
+    }
     let auth_secret = generate_random_secret();
 
-    let env_content = format!(
-        "# Auth (GitHub OAuth)\nAUTH_SECRET={}\nAUTH_GITHUB_ID=\nAUTH_GITHUB_SECRET=\n",
-        auth_secret
-    );
+    let env_content = env_section(&auth_secret, providers);
 
-    fs::write(&env_path, env_content)?;
+    output.fs().write(&env_path, env_content.as_bytes())?;
     Ok(())
 }
 
@@ -214,16 +348,18 @@ mod tests {
 
     #[test]
     fn get_files_to_skip_no_features() {
-        let skip = get_files_to_skip(false, false, false);
+        let skip = get_files_to_skip(false, false, false, Orm::Drizzle);
         assert!(skip.contains(&"src/routes/docs"));
+        assert!(skip.contains(&"drizzle.config.ts"));
         assert!(skip.contains(&"src/lib/auth.ts"));
         assert!(skip.contains(&"src/lib/db.ts"));
     }
 
     #[test]
     fn get_files_to_skip_all_features() {
-        let skip = get_files_to_skip(true, true, true);
-        assert!(skip.is_empty());
+        // Only the ORM that wasn't picked is left out
+        let skip = get_files_to_skip(true, true, true, Orm::Drizzle);
+        assert_eq!(skip, Orm::Kysely.files());
     }
 
     #[test]
@@ -236,6 +372,23 @@ mod tests {
         assert!(should_skip_file("src/routes/docs/index.tsx", &["src/routes/docs"]));
     }
 
+    #[test]
+    fn should_skip_file_respects_path_boundaries() {
+        assert!(!should_skip_file("src/lib/db.tsx", &["src/lib/db.ts"]));
+        assert!(!should_skip_file("src/routes/docs-old/a.tsx", &["src/routes/docs"]));
+    }
+
+    #[test]
+    fn should_skip_file_covers_flat_routes() {
+        let skip = get_files_to_skip(false, false, false, Orm::Raw);
+        for route in ["src/routes/docs.tsx", "src/routes/docs.$slug.tsx", "src/routes/docs.index.jsx"] {
+            assert!(should_skip_file(route, &skip), "{route}");
+        }
+        assert!(should_skip_file("api/auth.ts", &skip));
+        assert!(should_skip_file("api/auth.$.ts", &skip));
+        assert!(!should_skip_file("src/routes/index.tsx", &skip));
+    }
+
     #[test]
     fn should_skip_file_no_match() {
         assert!(!should_skip_file("src/main.tsx", &["src/lib/db.ts", "src/routes/docs"]));
@@ -262,6 +415,43 @@ mod tests {
         assert!(skip.contains(&"tsconfig.json"));
     }
 
+    #[test]
+    fn update_package_json_swaps_db_driver() {
+        use crate::vfs::MemoryFs;
+
+        let mut fs = MemoryFs::new();
+        let dir = Path::new("/app");
+        fs.create_dir_all(dir).unwrap();
+        fs.write(
+            &dir.join("package.json"),
+            br#"{"dependencies":{"pg":"^8.13.0","react":"^19"},"devDependencies":{"@types/pg":"^8.11.0"}}"#,
+        )
+        .unwrap();
+
+        update_package_json(&mut fs, dir, "typescript", true, true, Database::Sqlite, Orm::Raw).unwrap();
+        let package: serde_json::Value =
+            serde_json::from_str(&fs.read_to_string(&dir.join("package.json")).unwrap()).unwrap();
+        assert!(package["dependencies"].get("pg").is_none());
+        assert!(package["devDependencies"].get("@types/pg").is_none());
+        assert!(package["dependencies"]["better-sqlite3"].is_string());
+        assert!(package["devDependencies"]["@types/better-sqlite3"].is_string());
+        assert_eq!(package["dependencies"]["react"], "^19");
+
+        update_package_json(&mut fs, dir, "typescript", true, true, Database::Sqlite, Orm::Drizzle).unwrap();
+        let package: serde_json::Value =
+            serde_json::from_str(&fs.read_to_string(&dir.join("package.json")).unwrap()).unwrap();
+        assert!(package["dependencies"]["drizzle-orm"].is_string());
+        assert_eq!(package["scripts"]["db:migrate"], "drizzle-kit migrate");
+
+        update_package_json(&mut fs, dir, "typescript", true, true, Database::None, Orm::Drizzle).unwrap();
+        let package: serde_json::Value =
+            serde_json::from_str(&fs.read_to_string(&dir.join("package.json")).unwrap()).unwrap();
+        assert!(package["dependencies"].get("better-sqlite3").is_none());
+        assert!(package["dependencies"].get("mysql2").is_none());
+        assert!(package["dependencies"].get("drizzle-orm").is_none());
+        assert!(package["scripts"].get("db:migrate").is_none());
+    }
+
     #[test]
     fn generate_random_secret_length() {
         let secret = generate_random_secret();
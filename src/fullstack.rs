@@ -1,67 +1,111 @@
 use anyhow::Result;
-use std::fs;
 use std::path::Path;
 use walkdir::WalkDir;
 
-use crate::features::{should_skip_file, get_files_to_skip, update_package_json, cleanup_layout_for_no_auth, generate_env_file};
-use crate::template::process_template;
+use crate::auth::{provider_skips, write_provider_files};
+use crate::features::{should_skip_file, get_files_to_skip, update_package_json, update_auth_packages, cleanup_layout_for_no_auth, generate_env_file, ROOT_LAYOUTS};
+use crate::database::{db_module_variants, wire_rust_database, Database};
+use crate::language::{convert_package_json, rewrite_references, select_variants};
+use crate::orm::Orm;
+use crate::pm::PackageManager;
+use crate::scaffold::{Features, Language};
+use crate::template::{copy_entry, is_git_dir, OutputDir, TemplateVars};
 
 const FULLSTACK_SUBDIR: &str = "fullstack";
 
 /// Scaffold a fullstack project:
 /// 1. Copy frontend files to {output}/web/
 /// 2. Copy fullstack overlay files to {output}/
+/// 3. Generate web/.env and provider configs for auth
+/// 4. Wire the database into the Rust API
 pub fn scaffold_fullstack(
     temp_path: &Path,
-    output_path: &Path,
-    project_name: &str,
-    include_auth: bool,
-    include_docs: bool,
-) -> Result<()> {
-    fs::create_dir_all(output_path)?;
+    output: &mut OutputDir,
+    vars: &TemplateVars,
+    features: &Features,
+    pm: PackageManager,
+) -> Result<Vec<String>> {
+    let (include_auth, include_docs) = (features.auth, features.docs);
+    let output_path = output.root().to_path_buf();
+    output.fs().create_dir_all(&output_path)?;
 
     let web_path = output_path.join("web");
 
-    // Step 1: Copy frontend files to web/
-    let files_to_skip = get_files_to_skip(include_docs, include_auth, false);
-    copy_filtered(temp_path, &web_path, project_name, &files_to_skip, &[FULLSTACK_SUBDIR, "template.json"])?;
+    // Step 1: Copy frontend files to web/, picking the variants for the language.
+    // The database belongs to the Rust API, so the frontend never carries a driver.
+    let lang = features.lang.as_str();
+    let is_js = features.lang == Language::JavaScript;
+    let providers = features.providers();
+    let extra_skips: Vec<String> = db_module_variants()
+        .into_iter()
+        .chain(provider_skips(&providers))
+        .collect();
+    let mut files_to_skip: Vec<&str> = get_files_to_skip(include_docs, include_auth, false, Orm::Raw);
+    files_to_skip.extend(extra_skips.iter().map(String::as_str));
+    files_to_skip.extend(pm.foreign_lockfiles());
+    files_to_skip.push(FULLSTACK_SUBDIR);
+    let variants = select_variants(temp_path, features.lang, &files_to_skip)?;
+    files_to_skip.extend(variants.skip_patterns());
+    copy_filtered(temp_path, &web_path, vars, &files_to_skip, &[FULLSTACK_SUBDIR, "template.json"], output)?;
+    if is_js {
+        rewrite_references(output, &variants.renamed)?;
+    }
 
     // Update web/package.json (remove deps for disabled features)
-    update_package_json(&web_path, include_docs, include_auth, false)?;
+    if !output.report.was_skipped("web/package.json") {
+        update_package_json(output.fs(), &web_path, lang, include_docs, include_auth, Database::None, Orm::Raw)?;
+        update_auth_packages(output.fs(), &web_path, &providers)?;
+        if is_js {
+            convert_package_json(output.fs(), &web_path, &variants.renamed)?;
+        }
+        output.record_edit(&web_path.join("package.json"));
+    }
 
-    if !include_auth {
-        cleanup_layout_for_no_auth(&web_path)?;
+    let layout_kept = ROOT_LAYOUTS
+        .iter()
+        .any(|layout| output.report.was_skipped(Path::new("web").join(layout)));
+    if !include_auth && !layout_kept {
+        if let Some(layout) = cleanup_layout_for_no_auth(output.fs(), &web_path)? {
+            output.record_edit(&layout);
+        }
     }
 
     // Step 2: Copy fullstack overlay files to root
     let fullstack_src = temp_path.join(FULLSTACK_SUBDIR);
     if fullstack_src.is_dir() {
-        copy_filtered(&fullstack_src, output_path, project_name, &[], &["template.json"])?;
+        copy_filtered(&fullstack_src, &output_path, vars, &[], &["template.json"], output)?;
     }
 
-    // Step 3: Generate .env for auth in web/ if needed
+    // Step 3: Generate .env and provider configs for auth in web/ if needed
+    let mut warnings: Vec<String> = variants.warning().into_iter().collect();
     if include_auth {
-        generate_env_file(&web_path)?;
+        generate_env_file(&web_path, output, &providers)?;
+        warnings.extend(write_provider_files(output, &web_path, &providers, !is_js)?);
     }
 
-    Ok(())
+    // Step 4: Wire the database into the Rust API
+    let db_name = vars.get("crate_name").map(String::as_str).unwrap_or("app");
+    warnings.extend(wire_rust_database(output, features.db, db_name)?);
+
+    Ok(warnings)
 }
 
 fn copy_filtered(
     src: &Path,
     dest: &Path,
-    project_name: &str,
+    vars: &TemplateVars,
     files_to_skip: &[&str],
     extra_skip: &[&str],
+    output: &mut OutputDir,
 ) -> Result<()> {
-    fs::create_dir_all(dest)?;
+    output.fs().create_dir_all(dest)?;
 
     for entry in WalkDir::new(src).min_depth(1) {
         let entry = entry?;
         let relative_path = entry.path().strip_prefix(src)?;
         let relative_str = relative_path.to_string_lossy();
 
-        if should_skip_file(&relative_str, files_to_skip) {
+        if should_skip_file(&relative_str, files_to_skip) || is_git_dir(relative_path) {
             continue;
         }
 
@@ -72,23 +116,7 @@ fn copy_filtered(
         }
 
         let dest_path = dest.join(relative_path);
-
-        if entry.file_type().is_dir() {
-            fs::create_dir_all(&dest_path)?;
-        } else {
-            if let Some(parent) = dest_path.parent() {
-                fs::create_dir_all(parent)?;
-            }
-            match fs::read_to_string(entry.path()) {
-                Ok(content) => {
-                    let processed = process_template(&content, project_name);
-                    fs::write(&dest_path, processed)?;
-                }
-                Err(_) => {
-                    fs::copy(entry.path(), &dest_path)?;
-                }
-            }
-        }
+        copy_entry(&entry, &dest_path, vars, output)?;
     }
 
     Ok(())
@@ -0,0 +1,294 @@
+use anyhow::Result;
+use std::path::Path;
+use std::process::Command;
+
+use crate::env::is_secret_env_file;
+
+pub const DEFAULT_COMMIT_MESSAGE: &str = "Initial commit from nullslate";
+
+/// How the new project's repository is set up.
+#[derive(Clone, Debug)]
+pub struct GitOptions {
+    /// Initial branch name. Defaults to git's own `init.defaultBranch`.
+    pub branch: Option<String>,
+    /// Commit author as `Name <email>`. Also used as the committer, so the
+    /// commit succeeds even when `user.name`/`user.email` aren't configured.
+    pub author: Option<String>,
+    pub message: String,
+    /// Stage and commit the scaffolded files. When false the repo is only
+    /// initialized.
+    pub commit: bool,
+    /// URL added as the `origin` remote.
+    pub remote: Option<String>,
+}
+
+impl Default for GitOptions {
+    fn default() -> Self {
+        Self {
+            branch: None,
+            author: None,
+            message: DEFAULT_COMMIT_MESSAGE.to_string(),
+            commit: true,
+            remote: None,
+        }
+    }
+}
+
+/// How far `init_repo` got.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GitOutcome {
+    pub initialized: bool,
+    pub committed: bool,
+}
+
+impl GitOptions {
+    /// Settings that only apply to a new repository, so they can be
+    /// reported when there won't be one.
+    pub fn repo_settings(&self) -> Vec<&'static str> {
+        let mut set = Vec::new();
+        if self.branch.is_some() {
+            set.push("branch");
+        }
+        if self.author.is_some() {
+            set.push("author");
+        }
+        if self.message != DEFAULT_COMMIT_MESSAGE {
+            set.push("commit message");
+        }
+        if self.remote.is_some() {
+            set.push("remote");
+        }
+        set
+    }
+}
+
+/// Whether `path` is already inside a git work tree. A path that doesn't
+/// exist yet is checked through its nearest existing ancestor.
+pub fn is_inside_git_work_tree(path: &Path) -> bool {
+    let Some(existing) = path.ancestors().find(|p| p.is_dir()) else {
+        return false;
+    };
+    Command::new("git")
+        .args(["rev-parse", "--is-inside-work-tree"])
+        .current_dir(existing)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+/// Split `Name <email>` into its parts.
+pub fn parse_author(author: &str) -> Result<(String, String)> {
+    let parsed = author
+        .trim()
+        .strip_suffix('>')
+        .and_then(|rest| rest.split_once('<'))
+        .map(|(name, email)| (name.trim().to_string(), email.trim().to_string()))
+        .filter(|(name, email)| !name.is_empty() && !email.is_empty());
+    parsed.ok_or_else(|| anyhow::anyhow!("Invalid author '{author}', expected 'Name <email>'"))
+}
+
+/// Initialize a repository in `path`, then optionally add a remote and make
+/// the initial commit. Stops at the first failing git command and returns
+/// its stderr; the outcome says which steps completed before that.
+pub fn init_repo(path: &Path, options: &GitOptions) -> (GitOutcome, Result<()>) {
+    let mut outcome = GitOutcome::default();
+    let result = run_steps(path, options, &mut outcome);
+    (outcome, result)
+}
+
+fn run_steps(path: &Path, options: &GitOptions, outcome: &mut GitOutcome) -> Result<()> {
+    let author = options.author.as_deref().map(parse_author).transpose()?;
+
+    git(path, &["init"], None)?;
+    outcome.initialized = true;
+
+    if let Some(branch) = &options.branch {
+        // Works on every git version, unlike `git init --initial-branch`
+        git(path, &["symbolic-ref", "HEAD", &format!("refs/heads/{branch}")], None)?;
+    }
+
+    if let Some(remote) = &options.remote {
+        git(path, &["remote", "add", "origin", remote], None)?;
+    }
+
+    if options.commit {
+        check_env_files_ignored(path)?;
+        git(path, &["add", "."], None)?;
+        git(path, &["commit", "-m", &options.message], author.as_ref())?;
+        outcome.committed = true;
+    }
+
+    Ok(())
+}
+
+/// Refuse to stage env files that `.gitignore` doesn't cover, so a template
+/// with a broken ignore list can't commit live secrets.
+fn check_env_files_ignored(path: &Path) -> Result<()> {
+    let output = Command::new("git")
+        .args(["ls-files", "--others", "--exclude-standard"])
+        .current_dir(path)
+        .output()
+        .map_err(|e| anyhow::anyhow!("Failed to run git ls-files: {e}"))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git ls-files failed:\n{}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let exposed: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|file| {
+            Path::new(file)
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(is_secret_env_file)
+        })
+        .map(str::to_string)
+        .collect();
+    if !exposed.is_empty() {
+        anyhow::bail!(
+            "Refusing to commit {} because .gitignore doesn't cover {}. Add `.env*`, `!.env.example` and `!.env.enc` to .gitignore, then commit.",
+            exposed.join(", "),
+            if exposed.len() == 1 { "it" } else { "them" }
+        );
+    }
+    Ok(())
+}
+
+fn git(path: &Path, args: &[&str], identity: Option<&(String, String)>) -> Result<()> {
+    let mut command = Command::new("git");
+    command
+        .args(args)
+        .current_dir(path)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::piped());
+    if let Some((name, email)) = identity {
+        command
+            .env("GIT_AUTHOR_NAME", name)
+            .env("GIT_AUTHOR_EMAIL", email)
+            .env("GIT_COMMITTER_NAME", name)
+            .env("GIT_COMMITTER_EMAIL", email);
+    }
+
+    let display = format!("git {}", args.join(" "));
+    let output = command
+        .output()
+        .map_err(|e| anyhow::anyhow!("Failed to run {display}: {e}"))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("{display} failed:\n{}", stderr.trim());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn test_options() -> GitOptions {
+        GitOptions {
+            author: Some("Test <test@example.com>".to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn git_output(path: &Path, args: &[&str]) -> String {
+        let output = Command::new("git").args(args).current_dir(path).output().unwrap();
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    }
+
+    #[test]
+    fn parse_author_forms() {
+        assert_eq!(
+            parse_author("Jane Doe <jane@example.com>").unwrap(),
+            ("Jane Doe".to_string(), "jane@example.com".to_string())
+        );
+        assert!(parse_author("jane@example.com").is_err());
+        assert!(parse_author("<jane@example.com>").is_err());
+    }
+
+    #[test]
+    fn init_commits_on_requested_branch() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("README.md"), "hi\n").unwrap();
+        let options = GitOptions {
+            branch: Some("trunk".to_string()),
+            remote: Some("https://example.com/repo.git".to_string()),
+            ..test_options()
+        };
+
+        let (outcome, result) = init_repo(dir.path(), &options);
+        result.unwrap();
+        assert_eq!(outcome, GitOutcome { initialized: true, committed: true });
+        assert_eq!(git_output(dir.path(), &["branch", "--show-current"]), "trunk");
+        assert_eq!(git_output(dir.path(), &["log", "--format=%an"]), "Test");
+        assert_eq!(
+            git_output(dir.path(), &["remote", "get-url", "origin"]),
+            "https://example.com/repo.git"
+        );
+    }
+
+    #[test]
+    fn no_commit_only_initializes() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("README.md"), "hi\n").unwrap();
+        let options = GitOptions {
+            commit: false,
+            ..test_options()
+        };
+
+        let (outcome, result) = init_repo(dir.path(), &options);
+        result.unwrap();
+        assert_eq!(outcome, GitOutcome { initialized: true, committed: false });
+        assert!(dir.path().join(".git").is_dir());
+        assert_eq!(git_output(dir.path(), &["rev-list", "--all"]), "");
+    }
+
+    #[test]
+    fn failing_step_reports_stderr() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = GitOptions {
+            branch: Some("bad..name".to_string()),
+            ..test_options()
+        };
+
+        let (outcome, result) = init_repo(dir.path(), &options);
+        let message = format!("{:#}", result.unwrap_err());
+        assert!(outcome.initialized);
+        assert!(!outcome.committed);
+        assert!(message.contains("git symbolic-ref"), "{message}");
+    }
+
+    #[test]
+    fn unignored_env_files_are_not_committed() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("web")).unwrap();
+        fs::write(dir.path().join("web/.env"), "AUTH_SECRET=live\n").unwrap();
+        fs::write(dir.path().join("web/.env.example"), "AUTH_SECRET=\n").unwrap();
+
+        let (outcome, result) = init_repo(dir.path(), &test_options());
+        let message = format!("{:#}", result.unwrap_err());
+        assert!(outcome.initialized && !outcome.committed);
+        assert!(message.contains("web/.env because"), "{message}");
+
+        fs::write(dir.path().join(".gitignore"), ".env*\n!.env.example\n").unwrap();
+        let (outcome, result) = init_repo(dir.path(), &test_options());
+        result.unwrap();
+        assert!(outcome.committed);
+        let files = git_output(dir.path(), &["ls-files"]);
+        assert!(files.contains("web/.env.example"), "{files}");
+        assert!(!files.lines().any(|f| f == "web/.env"), "{files}");
+    }
+
+    #[test]
+    fn missing_path_checks_nearest_ancestor() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_, result) = init_repo(dir.path(), &GitOptions { commit: false, ..Default::default() });
+        result.unwrap();
+        assert!(is_inside_git_work_tree(&dir.path().join("not/yet/created")));
+    }
+}
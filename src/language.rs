@@ -0,0 +1,440 @@
+use anyhow::Result;
+use regex::Regex;
+use std::collections::BTreeSet;
+use std::path::Path;
+use walkdir::WalkDir;
+
+use crate::features::should_skip_file;
+use crate::scaffold::Language;
+use crate::template::OutputDir;
+use crate::vfs::FileSystem;
+
+/// TypeScript extensions and the JavaScript extension of their variant.
+const VARIANT_EXTENSIONS: &[(&str, &str)] = &[("ts", "js"), ("tsx", "jsx"), ("mts", "mjs"), ("cts", "cjs")];
+
+/// Declaration files have no JavaScript variant; they're dropped for JS.
+const DECLARATION_SUFFIXES: &[&str] = &[".d.ts", ".d.mts", ".d.cts"];
+
+/// Config files that come in a TypeScript and a JavaScript flavour.
+const CONFIG_VARIANTS: &[(&str, &str)] = &[("tsconfig.json", "jsconfig.json")];
+
+/// Which template files to drop for the chosen language.
+///
+/// Templates ship language-specific variants side by side (`foo.ts` next to
+/// `foo.js`); the one for the other language is skipped. TypeScript files
+/// without a JavaScript variant can't be used in a JavaScript project and
+/// are reported in `untranslated`.
+#[derive(Debug, Default)]
+pub struct VariantSelection {
+    pub skip: Vec<String>,
+    /// `(typescript, javascript)` file names for every TS file replaced by
+    /// its JS variant, used to fix up references in other files.
+    pub renamed: Vec<(String, String)>,
+    pub untranslated: Vec<String>,
+}
+
+impl VariantSelection {
+    pub fn skip_patterns(&self) -> impl Iterator<Item = &str> {
+        self.skip.iter().map(String::as_str)
+    }
+
+    /// Warning for TS files a JavaScript project ended up with anyway.
+    pub fn warning(&self) -> Option<String> {
+        if self.untranslated.is_empty() {
+            return None;
+        }
+        Some(format!(
+            "{} TypeScript file(s) have no JavaScript variant in the template and were copied as-is:\n  {}",
+            self.untranslated.len(),
+            self.untranslated.join("\n  ")
+        ))
+    }
+}
+
+fn split_extension(path: &str) -> Option<(&str, &str)> {
+    let (stem, ext) = path.rsplit_once('.')?;
+    if stem.is_empty() || stem.ends_with('/') {
+        return None;
+    }
+    Some((stem, ext))
+}
+
+fn is_declaration(path: &str) -> bool {
+    DECLARATION_SUFFIXES.iter().any(|suffix| path.ends_with(suffix))
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// The JavaScript variant of a TypeScript source path.
+fn js_variant(path: &str) -> Option<String> {
+    if let Some((ts, js)) = CONFIG_VARIANTS.iter().find(|(ts, _)| file_name(path) == *ts) {
+        return Some(format!("{}{}", &path[..path.len() - ts.len()], js));
+    }
+    if is_declaration(path) {
+        return None;
+    }
+    let (stem, ext) = split_extension(path)?;
+    let (_, js_ext) = VARIANT_EXTENSIONS.iter().find(|(ts, _)| *ts == ext)?;
+    Some(format!("{stem}.{js_ext}"))
+}
+
+/// The TypeScript variant of a JavaScript source path.
+fn ts_variant(path: &str) -> Option<String> {
+    if let Some((ts, js)) = CONFIG_VARIANTS.iter().find(|(_, js)| file_name(path) == *js) {
+        return Some(format!("{}{}", &path[..path.len() - js.len()], ts));
+    }
+    ts_source_variant(path)
+}
+
+/// Like `ts_variant`, for source files only.
+fn ts_source_variant(path: &str) -> Option<String> {
+    let (stem, ext) = split_extension(path)?;
+    let (ts_ext, _) = VARIANT_EXTENSIONS.iter().find(|(_, js)| *js == ext)?;
+    Some(format!("{stem}.{ts_ext}"))
+}
+
+/// Pick the files to skip from `template` for `lang`. Paths already matched
+/// by `skip` are left out of consideration, but still count as variants.
+///
+/// Skip lists only name the TypeScript side: a JavaScript source whose
+/// TypeScript variant is skipped is skipped too.
+pub fn select_variants(template: &Path, lang: Language, skip: &[&str]) -> Result<VariantSelection> {
+    let mut all = BTreeSet::new();
+    let walker = WalkDir::new(template)
+        .min_depth(1)
+        .into_iter()
+        .filter_entry(|e| e.file_name() != ".git" && e.file_name() != "node_modules");
+    for entry in walker {
+        let entry = entry?;
+        if entry.file_type().is_dir() {
+            continue;
+        }
+        let relative = entry.path().strip_prefix(template)?;
+        all.insert(relative.to_string_lossy().replace('\\', "/"));
+    }
+    let mut selection = VariantSelection::default();
+    let mut files = BTreeSet::new();
+    for path in all.iter().filter(|path| !should_skip_file(path, skip)) {
+        if ts_source_variant(path).is_some_and(|ts| should_skip_file(&ts, skip)) {
+            selection.skip.push(path.clone());
+        } else {
+            files.insert(path);
+        }
+    }
+
+    for path in files {
+        match lang {
+            Language::JavaScript => {
+                if is_declaration(path) {
+                    selection.skip.push(path.clone());
+                    continue;
+                }
+                let Some(js) = js_variant(path) else {
+                    continue;
+                };
+                if all.contains(&js) {
+                    selection.skip.push(path.clone());
+                    selection
+                        .renamed
+                        .push((file_name(path).to_string(), file_name(&js).to_string()));
+                } else if CONFIG_VARIANTS.iter().any(|(ts, _)| file_name(path) == *ts) {
+                    // A missing jsconfig.json is fine; a tsconfig.json is just noise
+                    selection.skip.push(path.clone());
+                } else {
+                    selection.untranslated.push(path.clone());
+                }
+            }
+            Language::TypeScript => {
+                if ts_variant(path).is_some_and(|ts| all.contains(&ts)) {
+                    selection.skip.push(path.clone());
+                }
+            }
+        }
+    }
+    // References are matched by file name, so a name shared with a file that
+    // stays TypeScript can't be rewritten safely
+    let untranslated = &selection.untranslated;
+    selection
+        .renamed
+        .retain(|(ts, _)| !untranslated.iter().any(|path| file_name(path) == ts));
+    selection.renamed.sort();
+    selection.renamed.dedup();
+    Ok(selection)
+}
+
+/// Point references to replaced TypeScript files (`/src/main.tsx`,
+/// `"./tsup.config.ts"`) at their JavaScript variants in every written text
+/// file.
+pub fn rewrite_references(output: &mut OutputDir, renamed: &[(String, String)]) -> Result<()> {
+    if renamed.is_empty() {
+        return Ok(());
+    }
+    let patterns = renamed
+        .iter()
+        .map(|(ts, js)| {
+            let re = Regex::new(&format!(r#"([/"'`\s]){}\b"#, regex::escape(ts)))?;
+            Ok((re, format!("${{1}}{js}")))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let root = output.root().to_path_buf();
+    let written = output.report.written.clone();
+    for relative in written {
+        let path = root.join(&relative);
+        let Ok(content) = output.fs().read_to_string(&path) else {
+            continue;
+        };
+        let mut updated = content.clone();
+        for (re, replacement) in &patterns {
+            updated = re.replace_all(&updated, replacement.as_str()).into_owned();
+        }
+        if updated != content {
+            output.fs().write(&path, updated.as_bytes())?;
+            output.record_edit(&path);
+        }
+    }
+    Ok(())
+}
+
+/// `value` pointing at the JavaScript variant if it names a `renamed` file,
+/// else unchanged.
+fn renamed_path(value: &str, renamed: &[(String, String)]) -> String {
+    let (dir, name) = match value.rsplit_once('/') {
+        Some((dir, name)) => (Some(dir), name),
+        None => (None, value),
+    };
+    match renamed.iter().find(|(ts, _)| ts == name) {
+        Some((_, js)) => dir.map_or_else(|| js.clone(), |dir| format!("{dir}/{js}")),
+        None => value.to_string(),
+    }
+}
+
+/// Drop type declarations from an `exports` map and point source entries at
+/// their JavaScript variants.
+fn convert_exports(value: &mut serde_json::Value, renamed: &[(String, String)]) {
+    match value {
+        serde_json::Value::Object(map) => {
+            map.remove("types");
+            map.retain(|_, v| !v.as_str().is_some_and(is_declaration));
+            for v in map.values_mut() {
+                convert_exports(v, renamed);
+            }
+        }
+        serde_json::Value::String(s) => *s = renamed_path(s, renamed),
+        serde_json::Value::Array(items) => items.iter_mut().for_each(|v| convert_exports(v, renamed)),
+        _ => {}
+    }
+}
+
+/// `script` without its `&&`-chained `tsc` steps, or `None` when nothing is
+/// left. Other tools whose names start with `tsc` (`tsc-alias`) stay.
+fn without_tsc(script: &str) -> Option<String> {
+    let steps: Vec<&str> = script.split("&&").map(str::trim).collect();
+    let kept: Vec<&str> = steps
+        .iter()
+        .copied()
+        .filter(|step| step.split_whitespace().next() != Some("tsc"))
+        .collect();
+    if kept.len() == steps.len() {
+        return Some(script.to_string());
+    }
+    (!kept.is_empty()).then(|| kept.join(" && "))
+}
+
+/// Make a package.json consistent with a JavaScript project: no `types`
+/// entries, entry points and script arguments pointing at the `renamed`
+/// files' JavaScript variants, no declaration output and no TypeScript
+/// compiler.
+pub fn convert_package_json(fs: &mut dyn FileSystem, dir: &Path, renamed: &[(String, String)]) -> Result<()> {
+    let package_json_path = dir.join("package.json");
+    let content = fs.read_to_string(&package_json_path)?;
+    let mut package: serde_json::Value = serde_json::from_str(&content)?;
+
+    if let Some(object) = package.as_object_mut() {
+        object.remove("types");
+        object.remove("typings");
+        for key in ["main", "module", "browser"] {
+            if let Some(serde_json::Value::String(s)) = object.get_mut(key) {
+                *s = renamed_path(s, renamed);
+            }
+        }
+        if let Some(exports) = object.get_mut("exports") {
+            convert_exports(exports, renamed);
+        }
+    }
+
+    if let Some(scripts) = package.get_mut("scripts").and_then(|s| s.as_object_mut()) {
+        // Drop `tsc` steps; a script that was nothing but type checking goes
+        scripts.retain(|_, command| match command.as_str() {
+            Some(s) => match without_tsc(s) {
+                Some(rest) => {
+                    *command = serde_json::Value::String(rest);
+                    true
+                }
+                None => false,
+            },
+            None => true,
+        });
+        for command in scripts.values_mut() {
+            if let Some(s) = command.as_str() {
+                let converted = s
+                    .split(' ')
+                    .filter(|arg| *arg != "--dts")
+                    .map(|arg| renamed_path(arg, renamed))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                *command = serde_json::Value::String(converted);
+            }
+        }
+    }
+
+    if let Some(dev_deps) = package.get_mut("devDependencies").and_then(|d| d.as_object_mut()) {
+        dev_deps.remove("typescript");
+    }
+
+    let formatted = serde_json::to_string_pretty(&package)?;
+    fs.write(&package_json_path, formatted.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::template::WriteMode;
+    use crate::vfs::MemoryFs;
+    use std::fs;
+
+    fn template(files: &[&str]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for file in files {
+            let path = dir.path().join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn javascript_prefers_js_variants() {
+        let dir = template(&[
+            "src/index.ts",
+            "src/index.js",
+            "src/Button.tsx",
+            "src/Button.jsx",
+            "src/env.d.ts",
+            "src/only-ts.ts",
+            "tsconfig.json",
+            "jsconfig.json",
+        ]);
+        let selection = select_variants(dir.path(), Language::JavaScript, &[]).unwrap();
+        assert_eq!(
+            selection.skip,
+            ["src/Button.tsx", "src/env.d.ts", "src/index.ts", "tsconfig.json"]
+        );
+        assert_eq!(selection.untranslated, ["src/only-ts.ts"]);
+        assert!(selection
+            .renamed
+            .contains(&("Button.tsx".to_string(), "Button.jsx".to_string())));
+    }
+
+    #[test]
+    fn typescript_drops_js_variants() {
+        let dir = template(&["src/index.ts", "src/index.js", "src/plain.js", "tsconfig.json", "jsconfig.json"]);
+        let selection = select_variants(dir.path(), Language::TypeScript, &[]).unwrap();
+        assert_eq!(selection.skip, ["jsconfig.json", "src/index.js"]);
+        assert!(selection.untranslated.is_empty());
+    }
+
+    #[test]
+    fn skipped_typescript_still_drops_its_js_variant() {
+        let dir = template(&["src/lib/auth.ts", "src/lib/auth.js"]);
+        let selection =
+            select_variants(dir.path(), Language::TypeScript, &["src/lib/auth.ts"]).unwrap();
+        assert_eq!(selection.skip, ["src/lib/auth.js"]);
+
+        // Skip lists name the TypeScript file; its JavaScript variant follows
+        let selection =
+            select_variants(dir.path(), Language::JavaScript, &["src/lib/auth.ts"]).unwrap();
+        assert_eq!(selection.skip, ["src/lib/auth.js"]);
+        assert!(selection.renamed.is_empty());
+    }
+
+    #[test]
+    fn skipped_files_are_not_reported() {
+        let dir = template(&["src/__tests__/a.test.ts"]);
+        let selection = select_variants(dir.path(), Language::JavaScript, &["src/__tests__"]).unwrap();
+        assert!(selection.untranslated.is_empty());
+    }
+
+    #[test]
+    fn references_follow_renamed_files() {
+        let mut mem = MemoryFs::new();
+        let root = Path::new("app");
+        let mut output = OutputDir::with_fs(root, WriteMode::Create, &mut mem);
+        output.fs().create_dir_all(root).unwrap();
+        output
+            .fs()
+            .write(
+                &root.join("index.html"),
+                br#"<script type="module" src="/src/main.tsx"></script>"#,
+            )
+            .unwrap();
+        output.report.written.push("index.html".into());
+
+        let renamed = [("main.tsx".to_string(), "main.jsx".to_string())];
+        rewrite_references(&mut output, &renamed).unwrap();
+        assert_eq!(output.report.edited, [Path::new("index.html")]);
+        drop(output);
+        assert_eq!(
+            mem.read_to_string(&root.join("index.html")).unwrap(),
+            r#"<script type="module" src="/src/main.jsx"></script>"#
+        );
+    }
+
+    #[test]
+    fn package_json_drops_types() {
+        let mut mem = MemoryFs::new();
+        let dir = Path::new("lib");
+        mem.create_dir_all(dir).unwrap();
+        mem.write(
+            &dir.join("package.json"),
+            br#"{
+                "types": "dist/index.d.ts",
+                "main": "src/only-ts.ts",
+                "exports": {".": {"types": "./dist/index.d.ts", "import": "./dist/index.js"}},
+                "scripts": {
+                    "build": "tsup src/index.ts --dts",
+                    "typecheck": "tsc --noEmit",
+                    "bundle": "tsc -p . && vite build",
+                    "paths": "tsc-alias -p tsconfig.json",
+                    "codegen": "kysely-codegen --out-file src/db/types.ts"
+                },
+                "devDependencies": {"typescript": "^5", "tsup": "^8"}
+            }"#,
+        )
+        .unwrap();
+
+        // Only renamed files move; untranslated ones and files without a
+        // variant keep their names
+        let renamed = [("index.ts".to_string(), "index.js".to_string())];
+        convert_package_json(&mut mem, dir, &renamed).unwrap();
+
+        let package: serde_json::Value =
+            serde_json::from_str(&mem.read_to_string(&dir.join("package.json")).unwrap()).unwrap();
+        assert!(package.get("types").is_none());
+        assert_eq!(package["main"], "src/only-ts.ts");
+        assert_eq!(package["exports"]["."], serde_json::json!({"import": "./dist/index.js"}));
+        assert_eq!(
+            package["scripts"],
+            serde_json::json!({
+                "build": "tsup src/index.js",
+                "bundle": "vite build",
+                "paths": "tsc-alias -p tsconfig.json",
+                "codegen": "kysely-codegen --out-file src/db/types.ts"
+            })
+        );
+        assert_eq!(package["devDependencies"], serde_json::json!({"tsup": "^8"}));
+    }
+}
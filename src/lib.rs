@@ -0,0 +1,39 @@
+//! Scaffolding and project runner for the nullslate dev tooling ecosystem.
+//!
+//! The `nullslate`/`ns` binaries are a thin CLI over this crate. Embedders
+//! should start with [`Scaffolder`]; scaffolding never prompts or draws to
+//! the terminal. The [`runner`] and [`supervisor`] do write to it: they
+//! stream child process output and print task listings and timing tables,
+//! or emit [`Event`]s instead in jsonl mode.
+
+pub mod auth;
+pub mod cache;
+pub mod database;
+pub mod devforge;
+pub mod env;
+pub mod events;
+pub mod features;
+pub mod fullstack;
+pub mod git;
+pub mod language;
+pub mod manifest;
+pub mod naming;
+pub mod orm;
+pub mod pm;
+pub mod runner;
+pub mod scaffold;
+pub mod secrets;
+pub mod supervisor;
+pub mod tasks;
+pub mod template;
+pub mod versions;
+pub mod vfs;
+
+pub use auth::AuthProvider;
+pub use database::Database;
+pub use events::Event;
+pub use orm::Orm;
+pub use pm::PackageManager;
+pub use scaffold::{Features, Language, ProjectType, ScaffoldReport, Scaffolder, TemplateSource};
+pub use template::WriteMode;
+pub use versions::VersionPolicy;
@@ -1,42 +1,107 @@
 mod cli;
-mod features;
-mod fullstack;
-mod runner;
-mod template;
 mod ui;
 
 use anyhow::Result;
 use clap::Parser;
-use regex::Regex;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use cli::{Cli, Commands, InitArgs, default_template_url};
-use features::{
-    cleanup_layout_for_no_auth, generate_env_file, get_files_to_skip, get_lib_files_to_skip,
-    update_lib_package_json, update_package_json,
+use cli::{Cli, Commands, EnvCommand, InitArgs, OutputFormat};
+use nullslate_cli::{env, secrets};
+use nullslate_cli::events::{self, Event};
+use nullslate_cli::git::{parse_author, GitOptions, DEFAULT_COMMIT_MESSAGE};
+use nullslate_cli::naming::{slugify, ProjectName};
+use nullslate_cli::runner::RunOptions;
+use nullslate_cli::supervisor::ProcessFailed;
+use nullslate_cli::template::{check_output_dir, WriteMode};
+use nullslate_cli::vfs::{FileSystem, RealFs};
+use nullslate_cli::{
+    runner, Database, Features, Orm, ProjectType, Scaffolder, TemplateSource,
+    VersionPolicy,
 };
-use template::{clone_template, copy_template, init_git, install_deps};
 use ui::{
     create_spinner, outro_cancel, outro_success, outro_success_fullstack, outro_success_lib,
-    Feature, Language, LibFeature, ProjectType,
+    Feature, LibFeature, Spinner,
 };
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
+    events::set_jsonl(cli.json || cli.output == OutputFormat::Jsonl);
+    if matches!(cli.command, Commands::Dev { .. } | Commands::Build { .. } | Commands::Run { .. }) {
+        events::set_human_handler(ui::print_run_event);
+    }
+
+    let result = match cli.command {
+        Commands::Init(args) => cmd_init(*args),
+        Commands::Dev {
+            mode,
+            profile,
+            target,
+            args,
+        } => runner::cmd_dev(&RunOptions {
+            mode,
+            profile,
+            target,
+            args,
+            ..Default::default()
+        }),
+        Commands::Build {
+            mode,
+            no_cache,
+            jobs,
+            profile,
+            target,
+            args,
+        } => runner::cmd_build(&RunOptions {
+            mode,
+            cache: !no_cache,
+            jobs: jobs.map(usize::from),
+            profile,
+            target,
+            args,
+        }),
+        Commands::Run {
+            task,
+            mode,
+            no_cache,
+            jobs,
+            profile,
+        } => runner::cmd_run(
+            task.as_deref(),
+            &RunOptions {
+                mode,
+                cache: !no_cache,
+                jobs: jobs.map(usize::from),
+                profile,
+                target: None,
+                args: Vec::new(),
+            },
+        ),
+        Commands::Env { command } => cmd_env(command),
+    };
 
-    match cli.command {
-        Commands::Init(args) => cmd_init(args),
-        Commands::Dev => runner::cmd_dev(),
-        Commands::Build => runner::cmd_build(),
+    if let Err(ref e) = result {
+        events::emit(Event::Error {
+            message: format!("{e:#}"),
+        });
+        // Exit like the failed child did, so scripts and CI see its code
+        if let Some(failed) = e.downcast_ref::<ProcessFailed>() {
+            eprintln!("Error: {e:?}");
+            std::process::exit(failed.code);
+        }
     }
+    result
 }
 
-fn cmd_init(args: InitArgs) -> Result<()> {
+fn cmd_init(mut args: InitArgs) -> Result<()> {
+    // Nobody is there to answer prompts when output is machine-readable
+    if events::is_jsonl() {
+        args.yes = true;
+    }
     if !args.yes {
         ui::intro()?;
     }
 
-    let project_name = match args.name {
+    let mut project_name = match args.name {
         Some(ref name) => name.clone(),
         None => {
             if args.yes {
@@ -47,22 +112,37 @@ fn cmd_init(args: InitArgs) -> Result<()> {
         }
     };
 
-    let name_regex = Regex::new(r"^[a-z0-9][a-z0-9-]*[a-z0-9]$|^[a-z0-9]$")?;
-    if !name_regex.is_match(&project_name) {
-        let msg = format!(
-            "Invalid project name '{}'. Use lowercase letters, numbers, and hyphens only.",
-            project_name
-        );
-        outro_cancel(&msg);
-        anyhow::bail!("{}", msg);
+    if ProjectName::parse(&project_name).is_err() && !args.yes {
+        let suggestion = slugify(&project_name);
+        if !suggestion.is_empty() && ui::confirm_suggested_name(&project_name, &suggestion)? {
+            project_name = suggestion;
+        }
     }
 
-    let output_path = args.path.clone().unwrap_or_else(|| PathBuf::from(&project_name));
+    let name = match ProjectName::parse(&project_name) {
+        Ok(name) => name,
+        Err(e) => {
+            outro_cancel(&e.to_string());
+            return Err(e);
+        }
+    };
 
-    if output_path.exists() {
-        let msg = format!("Directory '{}' already exists", output_path.display());
-        outro_cancel(&msg);
-        anyhow::bail!("{}", msg);
+    let output_path = args
+        .path
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(name.dir_name()));
+
+    let write_mode = if args.force {
+        WriteMode::Force
+    } else if args.merge {
+        WriteMode::Merge
+    } else {
+        WriteMode::Create
+    };
+
+    if let Err(e) = check_output_dir(&output_path, write_mode) {
+        outro_cancel(&e.to_string());
+        return Err(e);
     }
 
     // Determine project type
@@ -78,161 +158,328 @@ fn cmd_init(args: InitArgs) -> Result<()> {
         ui::prompt_project_type()?
     };
 
-    let template_url = args
-        .template
-        .clone()
-        .unwrap_or_else(|| {
-            let pt = match project_type {
-                ProjectType::Fullstack => "fullstack",
-                ProjectType::Lib => "lib",
-                ProjectType::App => "app",
-            };
-            default_template_url(pt).to_string()
-        });
+    if let Err(e) = name.check_for(project_type) {
+        outro_cancel(&e.to_string());
+        return Err(e);
+    }
 
-    // Clone template
-    let temp_dir = tempfile::tempdir()?;
-    let temp_path = temp_dir.path();
+    let features = match project_type {
+        ProjectType::App | ProjectType::Fullstack => app_features(&args, project_type)?,
+        ProjectType::Lib => lib_features(&args)?,
+    };
 
-    let spinner = create_spinner("Fetching template...");
-    clone_template(&template_url, temp_path)?;
-    spinner.stop("Template fetched");
+    let mut scaffolder = Scaffolder::new(&project_name)
+        .project_type(project_type)
+        .features(features)
+        .output_path(&output_path)
+        .write_mode(write_mode)
+        .package_manager(args.pm.into())
+        .versions(if args.latest {
+            VersionPolicy::Latest
+        } else {
+            args.versions.into()
+        })
+        .git(!args.no_git)
+        .git_options(git_options(&args)?)
+        .install(!args.no_install);
+    if let Some(ref template) = args.template {
+        let source = if Path::new(template).is_dir() {
+            TemplateSource::Local(PathBuf::from(template))
+        } else {
+            TemplateSource::Git(template.clone())
+        };
+        scaffolder = scaffolder.template(source);
+    }
 
-    match project_type {
-        ProjectType::Lib => cmd_init_lib(&args, &project_name, &output_path, temp_path)?,
-        ProjectType::Fullstack => cmd_init_fullstack(&args, &project_name, &output_path, temp_path)?,
-        ProjectType::App => cmd_init_app(&args, &project_name, &output_path, temp_path)?,
+    let mut spinner: Option<Spinner> = None;
+    let scaffolder = scaffolder.on_progress(|event| {
+        match event {
+            Event::StepStarted { step } => {
+                spinner = Some(create_spinner(ui::step_messages(*step).0));
+            }
+            Event::StepFinished { step } => {
+                if let Some(s) = spinner.take() {
+                    s.stop(ui::step_messages(*step).1);
+                }
+            }
+            Event::StepFailed { message, .. } => {
+                if let Some(s) = spinner.take() {
+                    s.fail(message);
+                }
+            }
+            Event::GitSkipped { .. } => {
+                ui::log_info("Already inside a git work tree, skipping git init");
+            }
+            Event::Warning { message } => ui::log_warning(message),
+            _ => {}
+        }
+        events::emit(event.clone());
+    });
+
+    if args.dry_run {
+        let rendered = scaffolder.render_in_memory()?;
+        ui::outro_dry_run(&rendered);
+        return Ok(());
     }
 
-    if !args.no_git {
-        let spinner = create_spinner("Initializing git...");
-        init_git(&output_path)?;
-        spinner.stop("Git initialized");
+    let report = scaffolder.scaffold()?;
+
+    ui::report_copy(&report.files);
+
+    // The files are in place, but a failed commit mustn't look like success
+    if let Some(error) = &report.git_error {
+        let message = format!(
+            "Created {} at {}, but git setup failed: {}",
+            project_name,
+            output_path.display(),
+            error
+        );
+        outro_cancel(&message);
+        anyhow::bail!(message);
     }
 
     match project_type {
         ProjectType::Fullstack => {
-            if !args.no_install {
-                let spinner = create_spinner("Installing dependencies...");
-                install_deps(&output_path.join("web"))?;
-                spinner.stop("Dependencies installed");
+            outro_success_fullstack(&project_name, &output_path, &report)
+        }
+        ProjectType::Lib => outro_success_lib(
+            &project_name,
+            &output_path,
+            args.no_install,
+            args.pm.into(),
+            &report,
+        ),
+        ProjectType::App => outro_success(
+            &project_name,
+            &output_path,
+            args.no_install,
+            args.pm.into(),
+            &report,
+        ),
+    }
+
+    Ok(())
+}
+
+fn cmd_env(command: EnvCommand) -> Result<()> {
+    let cwd = std::env::current_dir()?;
+    let (root, dirs) = env::env_dirs(&cwd)?;
+    let mut fs = RealFs;
+    let display = |dir: &Path, file: &str| {
+        let path = dir.join(file);
+        path.strip_prefix(&root).unwrap_or(&path).display().to_string()
+    };
+
+    match command {
+        EnvCommand::Check => {
+            let mut problems = Vec::new();
+            for dir in &dirs {
+                let result = env::check(&fs, dir)?;
+                let file = display(dir, env::ENV_FILE);
+                if !result.missing.is_empty() {
+                    problems.push(format!("{file} is missing {}", result.missing.join(", ")));
+                }
+                if !result.empty.is_empty() {
+                    problems.push(format!("{file} has empty {}", result.empty.join(", ")));
+                }
+                if result.is_ok() {
+                    ui::log_success(&format!("{file} matches {}", display(dir, env::EXAMPLE_FILE)));
+                }
+            }
+            if !problems.is_empty() {
+                anyhow::bail!("Environment check failed: {}", problems.join("; "));
+            }
+        }
+        EnvCommand::Sync => {
+            for dir in &dirs {
+                let added = env::sync(&mut fs, dir)?;
+                let file = display(dir, env::ENV_FILE);
+                if added.is_empty() {
+                    ui::log_info(&format!("{file} is up to date"));
+                } else {
+                    ui::log_success(&format!("Added {} to {file}", added.join(", ")));
+                }
+            }
+        }
+        EnvCommand::Rotate { key } => {
+            let changed = env::rotate(&mut fs, &dirs, &key)?;
+            for path in &changed {
+                let file = path.strip_prefix(&root).unwrap_or(path).display().to_string();
+                ui::log_success(&format!("Rotated {key} in {file}"));
+            }
+            // Keep committed copies in step with the new secret
+            let encrypted: Vec<&Path> = changed
+                .iter()
+                .filter_map(|path| path.parent())
+                .filter(|dir| fs.exists(&dir.join(env::ENCRYPTED_FILE)))
+                .collect();
+            if !encrypted.is_empty() {
+                match secrets::load_key(&fs, &root)? {
+                    Some(key) => {
+                        for dir in encrypted {
+                            secrets::encrypt_dir(&mut fs, dir, &key)?;
+                            ui::log_success(&format!("Updated {}", display(dir, env::ENCRYPTED_FILE)));
+                        }
+                    }
+                    None => ui::log_warning("No env key available, so .env.enc still has the old value"),
+                }
+            }
+        }
+        EnvCommand::Encrypt => {
+            let (key, created) = secrets::load_or_create_key(&fs, &root)?;
+            if let Some(path) = created {
+                ui::log_info(&format!(
+                    "Created shared key {} at {}; anyone holding it can decrypt, so share it with teammates outside the repo",
+                    key.id(),
+                    path.display()
+                ));
+            }
+            let mut any = false;
+            for dir in &dirs {
+                if secrets::encrypt_dir(&mut fs, dir, &key)? {
+                    any = true;
+                    ui::log_success(&format!(
+                        "Encrypted {} into {}",
+                        display(dir, env::ENV_FILE),
+                        display(dir, env::ENCRYPTED_FILE)
+                    ));
+                }
+            }
+            if !any {
+                anyhow::bail!("No .env to encrypt");
             }
-            outro_success_fullstack(&project_name, &output_path);
         }
-        ProjectType::Lib => {
-            if !args.no_install {
-                let spinner = create_spinner("Installing dependencies...");
-                install_deps(&output_path)?;
-                spinner.stop("Dependencies installed");
+        EnvCommand::Show { mode } => {
+            for dir in &dirs {
+                let vars = env::load(&fs, &root, dir, &mode)?;
+                let name = dir.strip_prefix(&root).unwrap_or(dir).display().to_string();
+                let name = if name.is_empty() { ".".to_string() } else { name };
+                if events::is_jsonl() {
+                    for var in vars {
+                        events::emit(Event::EnvVar {
+                            dir: PathBuf::from(&name),
+                            value: env::mask(&var.key, &var.value),
+                            key: var.key,
+                            source: var.source,
+                        });
+                    }
+                    continue;
+                }
+                if dirs.len() > 1 {
+                    println!("# {name}");
+                }
+                let width = vars.iter().map(|var| var.key.len()).max().unwrap_or(0);
+                for var in &vars {
+                    let value = env::mask(&var.key, &var.value);
+                    println!("{:width$}  {value}  ({})", var.key, var.source);
+                }
             }
-            outro_success_lib(&project_name, &output_path, args.no_install);
         }
-        ProjectType::App => {
-            if !args.no_install {
-                let spinner = create_spinner("Installing dependencies...");
-                install_deps(&output_path)?;
-                spinner.stop("Dependencies installed");
+        EnvCommand::Decrypt { force } => {
+            let Some(key) = secrets::load_key(&fs, &root)? else {
+                anyhow::bail!("No env key found. {}", secrets::missing_key_hint(&fs, &root)?);
+            };
+            let mut any = false;
+            for dir in &dirs {
+                if secrets::decrypt_dir(&mut fs, dir, &key, force)? {
+                    any = true;
+                    ui::log_success(&format!(
+                        "Decrypted {} into {}",
+                        display(dir, env::ENCRYPTED_FILE),
+                        display(dir, env::ENV_FILE)
+                    ));
+                }
+            }
+            if !any {
+                anyhow::bail!("No .env.enc to decrypt");
             }
-            outro_success(&project_name, &output_path, args.no_install);
         }
     }
-
     Ok(())
 }
 
-fn cmd_init_app(
-    args: &InitArgs,
-    project_name: &str,
-    output_path: &PathBuf,
-    temp_path: &std::path::Path,
-) -> Result<()> {
-    let (include_auth, include_docs, include_db) = if args.yes {
-        (!args.no_auth, args.docs, args.db != "none")
-    } else {
-        let selected = ui::prompt_features()?;
-        (
-            selected.contains(&Feature::Auth),
-            selected.contains(&Feature::Docs),
-            selected.contains(&Feature::Db),
-        )
-    };
-
-    let spinner = create_spinner("Processing files...");
-
-    let files_to_skip = get_files_to_skip(include_docs, include_auth, include_db);
-    copy_template(temp_path, output_path, project_name, &files_to_skip)?;
-    update_package_json(output_path, include_docs, include_auth, include_db)?;
-
-    if !include_auth {
-        cleanup_layout_for_no_auth(output_path)?;
+fn git_options(args: &InitArgs) -> Result<GitOptions> {
+    if let Some(ref author) = args.author {
+        parse_author(author)?;
     }
+    Ok(GitOptions {
+        branch: args.branch.clone(),
+        author: args.author.clone(),
+        message: args
+            .message
+            .clone()
+            .unwrap_or_else(|| DEFAULT_COMMIT_MESSAGE.to_string()),
+        commit: !args.no_commit,
+        remote: args.remote.clone(),
+    })
+}
 
-    if include_auth {
-        generate_env_file(output_path)?;
+fn app_features(args: &InitArgs, project_type: ProjectType) -> Result<Features> {
+    if args.yes {
+        let (db, orm): (Database, Orm) = (args.db.into(), args.orm.into());
+        if orm != Orm::Raw && !db.is_enabled() {
+            anyhow::bail!("--orm {} needs a database; pass --db as well", orm.as_str());
+        }
+        if orm != Orm::Raw && project_type == ProjectType::Fullstack {
+            anyhow::bail!("--orm is for app projects; fullstack databases live in the Rust API");
+        }
+        return Ok(Features {
+            auth: !args.no_auth,
+            auth_providers: args.auth_providers.iter().map(|&p| p.into()).collect(),
+            docs: args.docs,
+            db,
+            orm,
+            lang: args.lang.into(),
+            ..Default::default()
+        });
     }
 
-    spinner.stop("Files processed");
-    Ok(())
-}
-
-fn cmd_init_fullstack(
-    args: &InitArgs,
-    project_name: &str,
-    output_path: &PathBuf,
-    temp_path: &std::path::Path,
-) -> Result<()> {
-    let (include_auth, include_docs) = if args.yes {
-        (!args.no_auth, args.docs)
+    let lang = ui::prompt_language()?;
+    let selected = ui::prompt_features()?;
+    let auth_providers = if selected.contains(&Feature::Auth) {
+        ui::prompt_auth_providers()?
     } else {
-        let selected = ui::prompt_features()?;
-        (
-            selected.contains(&Feature::Auth),
-            selected.contains(&Feature::Docs),
-        )
+        Vec::new()
     };
-
-    let spinner = create_spinner("Processing files...");
-
-    fullstack::scaffold_fullstack(temp_path, output_path, project_name, include_auth, include_docs)?;
-
-    spinner.stop("Files processed");
-    Ok(())
-}
-
-fn cmd_init_lib(
-    args: &InitArgs,
-    project_name: &str,
-    output_path: &PathBuf,
-    temp_path: &std::path::Path,
-) -> Result<()> {
-    let (lang, include_react, include_css, include_testing) = if args.yes {
-        (
-            args.lang.clone(),
-            args.react,
-            args.css,
-            args.testing,
-        )
+    let db = if selected.contains(&Feature::Db) {
+        ui::prompt_database(&Database::ENABLED)?
     } else {
-        let language = ui::prompt_language()?;
-        let selected = ui::prompt_lib_features()?;
-        (
-            match language {
-                Language::TypeScript => "typescript".to_string(),
-                Language::JavaScript => "javascript".to_string(),
-            },
-            selected.contains(&LibFeature::React),
-            selected.contains(&LibFeature::Css),
-            selected.contains(&LibFeature::Testing),
-        )
+        Database::None
     };
+    let orm = if db.is_enabled() && project_type == ProjectType::App {
+        ui::prompt_orm()?
+    } else {
+        Orm::Raw
+    };
+    Ok(Features {
+        auth: selected.contains(&Feature::Auth),
+        auth_providers,
+        docs: selected.contains(&Feature::Docs),
+        db,
+        orm,
+        lang,
+        ..Default::default()
+    })
+}
 
-    let spinner = create_spinner("Processing files...");
-
-    let files_to_skip =
-        get_lib_files_to_skip(&lang, include_react, include_css, include_testing);
-    copy_template(temp_path, output_path, project_name, &files_to_skip)?;
-    update_lib_package_json(output_path, &lang, include_react, include_css, include_testing)?;
+fn lib_features(args: &InitArgs) -> Result<Features> {
+    if args.yes {
+        return Ok(Features {
+            lang: args.lang.into(),
+            react: args.react,
+            css: args.css,
+            testing: args.testing,
+            ..Default::default()
+        });
+    }
 
-    spinner.stop("Files processed");
-    Ok(())
+    let lang = ui::prompt_language()?;
+    let selected = ui::prompt_lib_features()?;
+    Ok(Features {
+        lang,
+        react: selected.contains(&LibFeature::React),
+        css: selected.contains(&LibFeature::Css),
+        testing: selected.contains(&LibFeature::Testing),
+        ..Default::default()
+    })
 }
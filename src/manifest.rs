@@ -0,0 +1,171 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::vfs::FileSystem;
+
+pub const MANIFEST_FILE: &str = "nullslate.toml";
+
+/// Project-level settings recorded at scaffold time and read back by the
+/// runner, stored as `nullslate.toml` in the project root.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    #[serde(default)]
+    pub project: ProjectSection,
+    /// Versions of the managed packages as resolved by the initial install.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub packages: BTreeMap<String, String>,
+    /// Named tasks for `ns run`. `dev` and `build` replace the built-in
+    /// commands of the same name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tasks: BTreeMap<String, Task>,
+    /// Named settings for `ns dev`, `ns build` and `ns run`, picked with
+    /// `--profile`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProjectSection {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, rename = "type", skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub package_manager: Option<String>,
+}
+
+/// A `[tasks.<name>]` section.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Task {
+    /// Shell commands, run one after another.
+    #[serde(default)]
+    pub commands: Vec<String>,
+    /// Relative to the project root.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Tasks that must finish first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<String>,
+    /// Globs of the files the task reads, relative to `cwd`. A task with
+    /// inputs is skipped when none of them changed since a cached run.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub inputs: Vec<String>,
+    /// Globs of the files the task writes, restored on a cache hit.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub outputs: Vec<String>,
+}
+
+/// A `[profiles.<name>]` section. The cargo settings apply to the built-in
+/// Rust commands and `web_mode` to the built-in web ones.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    /// Set for every process, on top of the env files.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Cargo features to enable.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub features: Vec<String>,
+    /// Cargo target triple.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    /// Passed to the web dev server and build as `--mode`, which is Vite's flag.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub web_mode: Option<String>,
+}
+
+impl Manifest {
+    /// Read `nullslate.toml` from `dir`, if there is one.
+    pub fn load(dir: &Path) -> Result<Option<Manifest>> {
+        let path = dir.join(MANIFEST_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(&path)?;
+        Self::parse(&content)
+            .with_context(|| format!("Failed to parse {}", path.display()))
+            .map(Some)
+    }
+
+    pub fn parse(content: &str) -> Result<Manifest> {
+        Ok(toml::from_str(content)?)
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        Ok(toml::to_string_pretty(self)?)
+    }
+
+    pub fn write(&self, fs: &mut dyn FileSystem, dir: &Path) -> Result<()> {
+        fs.write(&dir.join(MANIFEST_FILE), self.to_toml()?.as_bytes())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let manifest = Manifest {
+            project: ProjectSection {
+                name: Some("my-app".to_string()),
+                kind: Some("app".to_string()),
+                package_manager: Some("pnpm".to_string()),
+            },
+            packages: BTreeMap::from([("@thesandybridge/ui".to_string(), "1.4.2".to_string())]),
+            ..Default::default()
+        };
+        let text = manifest.to_toml().unwrap();
+        assert!(text.contains("type = \"app\""));
+        assert!(text.contains("[packages]"));
+        assert_eq!(Manifest::parse(&text).unwrap(), manifest);
+    }
+
+    #[test]
+    fn parses_tasks() {
+        let manifest = Manifest::parse(
+            "[tasks.lint]\ncommands = [\"cargo clippy\", \"bun run lint\"]\n\n\
+             [tasks.ci]\ncwd = \"web\"\nenv = { CI = \"1\" }\ndepends_on = [\"lint\"]\n\
+             inputs = [\"src/**\", \"!src/**/*.snap\"]\noutputs = [\"dist\"]\n",
+        )
+        .unwrap();
+        assert_eq!(manifest.tasks["lint"].commands.len(), 2);
+        let ci = &manifest.tasks["ci"];
+        assert_eq!(ci.depends_on, ["lint"]);
+        assert_eq!(ci.env["CI"], "1");
+        assert!(ci.commands.is_empty());
+        assert_eq!(ci.inputs, ["src/**", "!src/**/*.snap"]);
+        assert_eq!(ci.outputs, ["dist"]);
+
+        let typo = Manifest::parse("[tasks.lint]\ncommand = \"x\"\n").unwrap_err();
+        assert!(format!("{typo:#}").contains("unknown field `command`"), "{typo:#}");
+    }
+
+    #[test]
+    fn parses_profiles() {
+        let manifest = Manifest::parse(
+            "[profiles.staging]\nenv = { API_URL = \"https://staging.example.com\" }\n\
+             features = [\"tracing\"]\ntarget = \"x86_64-unknown-linux-musl\"\nweb_mode = \"staging\"\n",
+        )
+        .unwrap();
+        let staging = &manifest.profiles["staging"];
+        assert_eq!(staging.features, ["tracing"]);
+        assert_eq!(staging.target.as_deref(), Some("x86_64-unknown-linux-musl"));
+        assert_eq!(staging.web_mode.as_deref(), Some("staging"));
+        assert_eq!(staging.env["API_URL"], "https://staging.example.com");
+        assert!(Manifest::parse("[profiles.x]\nfeature = [\"a\"]\n").is_err());
+    }
+
+    #[test]
+    fn load_missing_is_none() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(Manifest::load(dir.path()).unwrap().is_none());
+    }
+}
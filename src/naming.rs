@@ -0,0 +1,175 @@
+use anyhow::Result;
+use regex::Regex;
+
+use crate::scaffold::ProjectType;
+use crate::template::TemplateVars;
+
+const NAME_PATTERN: &str = r"^[a-z0-9][a-z0-9-]*[a-z0-9]$|^[a-z0-9]$";
+
+/// A validated project name, optionally npm-scoped (`@scope/name`), and the
+/// variants derived from it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProjectName {
+    scope: Option<String>,
+    bare: String,
+}
+
+impl ProjectName {
+    /// Parse `name`, accepting `@scope/name`. Whether a scope is allowed for
+    /// the project type is checked separately by [`ProjectName::check_for`].
+    pub fn parse(name: &str) -> Result<ProjectName> {
+        let (scope, bare) = match name.strip_prefix('@').and_then(|rest| rest.split_once('/')) {
+            Some((scope, bare)) => (Some(scope), bare),
+            None => (None, name),
+        };
+
+        let re = Regex::new(NAME_PATTERN)?;
+        let valid = re.is_match(bare) && scope.is_none_or(|s| re.is_match(s));
+        if !valid {
+            let suggestion = slugify(name);
+            let hint = if suggestion.is_empty() || suggestion == name {
+                String::new()
+            } else {
+                format!(" Try '{suggestion}'.")
+            };
+            anyhow::bail!(
+                "Invalid project name '{}'. Use lowercase letters, numbers, and hyphens only.{}",
+                name,
+                hint
+            );
+        }
+
+        Ok(ProjectName {
+            scope: scope.map(str::to_string),
+            bare: bare.to_string(),
+        })
+    }
+
+    /// Scoped names only make sense for packages that get published.
+    pub fn check_for(&self, project_type: ProjectType) -> Result<()> {
+        if self.scope.is_some() && project_type != ProjectType::Lib {
+            anyhow::bail!(
+                "Scoped names like '{}' are only supported for lib projects. Use '{}' instead.",
+                self.package_name(),
+                self.bare
+            );
+        }
+        Ok(())
+    }
+
+    pub fn scope(&self) -> Option<&str> {
+        self.scope.as_deref()
+    }
+
+    /// Directory to create: the bare part of a scoped name.
+    pub fn dir_name(&self) -> &str {
+        &self.bare
+    }
+
+    /// Name for package.json, including the scope.
+    pub fn package_name(&self) -> String {
+        match &self.scope {
+            Some(scope) => format!("@{}/{}", scope, self.bare),
+            None => self.bare.clone(),
+        }
+    }
+
+    /// Rust crate name: hyphens become underscores.
+    pub fn crate_name(&self) -> String {
+        self.bare.replace('-', "_")
+    }
+
+    /// PascalCase name for components and types.
+    pub fn component_name(&self) -> String {
+        self.bare
+            .split('-')
+            .filter(|part| !part.is_empty())
+            .map(|part| {
+                let mut chars = part.chars();
+                match chars.next() {
+                    Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                    None => String::new(),
+                }
+            })
+            .collect()
+    }
+
+    /// Placeholders for every variant. `project_name` is the bare name;
+    /// `package_name` keeps the scope.
+    pub fn template_vars(&self) -> TemplateVars {
+        TemplateVars::from([
+            ("project_name".to_string(), self.bare.clone()),
+            ("package_name".to_string(), self.package_name()),
+            ("dir_name".to_string(), self.dir_name().to_string()),
+            ("crate_name".to_string(), self.crate_name()),
+            ("component_name".to_string(), self.component_name()),
+        ])
+    }
+}
+
+/// Best-effort valid name for arbitrary input: `My App` becomes `my-app`.
+/// A leading `@scope/` is kept and slugified on its own.
+pub fn slugify(input: &str) -> String {
+    if let Some((scope, bare)) = input.trim().strip_prefix('@').and_then(|r| r.split_once('/')) {
+        let (scope, bare) = (slugify_part(scope), slugify_part(bare));
+        if !scope.is_empty() && !bare.is_empty() {
+            return format!("@{scope}/{bare}");
+        }
+        return bare;
+    }
+    slugify_part(input)
+}
+
+fn slugify_part(input: &str) -> String {
+    let mut slug = String::new();
+    for c in input.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scoped_name_variants() {
+        let name = ProjectName::parse("@acme/fancy-widgets").unwrap();
+        assert_eq!(name.scope(), Some("acme"));
+        assert_eq!(name.dir_name(), "fancy-widgets");
+        assert_eq!(name.package_name(), "@acme/fancy-widgets");
+        assert_eq!(name.crate_name(), "fancy_widgets");
+        assert_eq!(name.component_name(), "FancyWidgets");
+    }
+
+    #[test]
+    fn scope_only_for_libs() {
+        let name = ProjectName::parse("@acme/widgets").unwrap();
+        assert!(name.check_for(ProjectType::Lib).is_ok());
+        assert!(name.check_for(ProjectType::App).is_err());
+        assert!(ProjectName::parse("widgets")
+            .unwrap()
+            .check_for(ProjectType::App)
+            .is_ok());
+    }
+
+    #[test]
+    fn invalid_names_suggest_slug() {
+        let err = ProjectName::parse("My App").unwrap_err().to_string();
+        assert!(err.contains("Try 'my-app'"), "{err}");
+        assert!(ProjectName::parse("@Acme/x").is_err());
+        assert!(ProjectName::parse("@acme/").is_err());
+    }
+
+    #[test]
+    fn slugify_inputs() {
+        assert_eq!(slugify("My App"), "my-app");
+        assert_eq!(slugify("  --Hello__World!! "), "hello-world");
+        assert_eq!(slugify("@Acme Co/My Lib"), "@acme-co/my-lib");
+        assert_eq!(slugify("???"), "");
+    }
+}
@@ -0,0 +1,283 @@
+use anyhow::Result;
+use std::path::Path;
+
+use crate::database::Database;
+use crate::template::OutputDir;
+
+/// How an app project talks to its database.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Orm {
+    /// The driver on its own (`pg`, `better-sqlite3`, `mysql2`).
+    #[default]
+    Raw,
+    Drizzle,
+    Kysely,
+}
+
+impl Orm {
+    pub const ALL: [Orm; 3] = [Orm::Raw, Orm::Drizzle, Orm::Kysely];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Orm::Raw => "raw",
+            Orm::Drizzle => "drizzle",
+            Orm::Kysely => "kysely",
+        }
+    }
+
+    /// npm packages as `(name, version, dev)`, on top of the driver.
+    pub fn npm_packages(&self) -> &'static [(&'static str, &'static str, bool)] {
+        match self {
+            Orm::Raw => &[],
+            Orm::Drizzle => &[("drizzle-orm", "^0.44.0", false), ("drizzle-kit", "^0.31.0", true)],
+            Orm::Kysely => &[
+                ("kysely", "^0.28.0", false),
+                ("kysely-ctl", "^0.13.0", true),
+                ("kysely-codegen", "^0.18.0", true),
+            ],
+        }
+    }
+
+    /// `db:generate` and `db:migrate` package.json scripts.
+    pub fn scripts(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Orm::Raw => &[],
+            Orm::Drizzle => &[
+                ("db:generate", "drizzle-kit generate"),
+                ("db:migrate", "drizzle-kit migrate"),
+            ],
+            Orm::Kysely => &[
+                ("db:generate", "kysely-codegen --out-file src/db/types.ts"),
+                ("db:migrate", "kysely migrate:latest"),
+            ],
+        }
+    }
+
+    /// Template files that belong to this ORM and are dropped for the others.
+    /// JavaScript variants follow their TypeScript names.
+    pub fn files(&self) -> &'static [&'static str] {
+        match self {
+            Orm::Raw => &[],
+            Orm::Drizzle => &["drizzle.config.ts", "drizzle", "src/db/schema.ts"],
+            Orm::Kysely => &["kysely.config.ts", "src/db/types.ts", "src/db/migrations"],
+        }
+    }
+
+    /// Built-in `src/lib/db` module, used when the template has no variant.
+    pub(crate) fn db_module(&self, db: Database, typescript: bool) -> Option<String> {
+        let url = database_url(db, typescript);
+        let module = match (self, db) {
+            (Orm::Raw, _) | (_, Database::None) => return None,
+            (Orm::Drizzle, _) => {
+                let (driver, mode) = match db {
+                    Database::Sqlite => ("better-sqlite3", ""),
+                    Database::Mysql => ("mysql2", ", mode: \"default\""),
+                    _ => ("node-postgres", ""),
+                };
+                format!(
+                    "import {{ drizzle }} from \"drizzle-orm/{driver}\"\n\
+                     import * as schema from \"@/db/schema\"\n\n\
+                     export const db = drizzle({url}, {{ schema{mode} }})\n"
+                )
+            }
+            (Orm::Kysely, _) => {
+                let (dialect, import, setup) = match db {
+                    Database::Sqlite => (
+                        "SqliteDialect",
+                        "import Database from \"better-sqlite3\"",
+                        format!("new SqliteDialect({{ database: new Database({url}) }})"),
+                    ),
+                    Database::Mysql => (
+                        "MysqlDialect",
+                        "import { createPool } from \"mysql2\"",
+                        format!("new MysqlDialect({{ pool: createPool({url}) }})"),
+                    ),
+                    _ => (
+                        "PostgresDialect",
+                        "import { Pool } from \"pg\"",
+                        format!("new PostgresDialect({{ pool: new Pool({{ connectionString: {url} }}) }})"),
+                    ),
+                };
+                let (types, generic) = if typescript {
+                    ("import type { DB } from \"@/db/types\"\n", "<DB>")
+                } else {
+                    ("", "")
+                };
+                format!(
+                    "import {{ Kysely, {dialect} }} from \"kysely\"\n{import}\n{types}\n\
+                     export const db = new Kysely{generic}({{\n  dialect: {setup},\n}})\n"
+                )
+            }
+        };
+        Some(module)
+    }
+
+    /// Built-in config, schema and type files as `(path, contents)`.
+    fn support_files(&self, db: Database, typescript: bool) -> Vec<(String, String)> {
+        let ext = if typescript { "ts" } else { "js" };
+        match self {
+            Orm::Raw => Vec::new(),
+            Orm::Drizzle => {
+                let dialect = match db {
+                    Database::Sqlite => "sqlite",
+                    Database::Mysql => "mysql",
+                    _ => "postgresql",
+                };
+                let url = database_url(db, typescript);
+                let config = format!(
+                    "import {{ defineConfig }} from \"drizzle-kit\"\n\n\
+                     export default defineConfig({{\n  \
+                     dialect: \"{dialect}\",\n  \
+                     schema: \"./src/db/schema.{ext}\",\n  \
+                     out: \"./drizzle\",\n  \
+                     dbCredentials: {{ url: {url} }},\n\
+                     }})\n"
+                );
+                vec![
+                    (format!("drizzle.config.{ext}"), config),
+                    (format!("src/db/schema.{ext}"), drizzle_schema(db).to_string()),
+                ]
+            }
+            Orm::Kysely => {
+                let config = "import { defineConfig } from \"kysely-ctl\"\n\
+                              import { db } from \"./src/lib/db\"\n\n\
+                              export default defineConfig({\n  \
+                              kysely: db,\n  \
+                              migrations: { migrationFolder: \"src/db/migrations\" },\n\
+                              })\n";
+                let mut files = vec![(format!("kysely.config.{ext}"), config.to_string())];
+                if typescript {
+                    // Replaced by `db:generate` once the database has tables
+                    files.push((
+                        "src/db/types.ts".to_string(),
+                        "// Generated by kysely-codegen: run `db:generate`\nexport interface DB {}\n"
+                            .to_string(),
+                    ));
+                }
+                files
+            }
+        }
+    }
+}
+
+/// JavaScript expression for the connection string `db` expects. SQLite
+/// drivers want a file path, not the `sqlite://` URL kept in `.env`.
+fn database_url(db: Database, typescript: bool) -> String {
+    let bang = if typescript { "!" } else { "" };
+    match db {
+        Database::Sqlite => {
+            format!("process.env.DATABASE_URL{bang}.replace(/^sqlite:\\/\\//, \"\").split(\"?\")[0]")
+        }
+        _ => format!("process.env.DATABASE_URL{bang}"),
+    }
+}
+
+fn drizzle_schema(db: Database) -> &'static str {
+    match db {
+        Database::Sqlite => {
+            r#"import { integer, sqliteTable, text } from "drizzle-orm/sqlite-core"
+
+export const users = sqliteTable("users", {
+  id: integer("id").primaryKey({ autoIncrement: true }),
+  email: text("email").notNull().unique(),
+  createdAt: integer("created_at", { mode: "timestamp" }).notNull(),
+})
+"#
+        }
+        Database::Mysql => {
+            r#"import { mysqlTable, serial, timestamp, varchar } from "drizzle-orm/mysql-core"
+
+export const users = mysqlTable("users", {
+  id: serial("id").primaryKey(),
+  email: varchar("email", { length: 255 }).notNull().unique(),
+  createdAt: timestamp("created_at").defaultNow().notNull(),
+})
+"#
+        }
+        _ => {
+            r#"import { pgTable, serial, text, timestamp } from "drizzle-orm/pg-core"
+
+export const users = pgTable("users", {
+  id: serial("id").primaryKey(),
+  email: text("email").notNull().unique(),
+  createdAt: timestamp("created_at").defaultNow().notNull(),
+})
+"#
+        }
+    }
+}
+
+/// Write the ORM's config, schema and type files the template didn't ship
+/// in either language.
+pub fn write_orm_files(
+    output: &mut OutputDir,
+    db: Database,
+    orm: Orm,
+    typescript: bool,
+) -> Result<()> {
+    if !db.is_enabled() {
+        return Ok(());
+    }
+    let root = output.root().to_path_buf();
+    for (path, contents) in orm.support_files(db, typescript) {
+        let dest = root.join(&path);
+        let stem = Path::new(&path).with_extension("");
+        let shipped = ["ts", "js"]
+            .iter()
+            .any(|ext| output.fs().exists(&root.join(stem.with_extension(ext))));
+        if shipped {
+            continue;
+        }
+        output.fs().create_dir_all(dest.parent().unwrap())?;
+        if output.prepare_write(&dest)? {
+            output.fs().write(&dest, contents.as_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::template::WriteMode;
+    use crate::vfs::{FileSystem, MemoryFs};
+
+    #[test]
+    fn drizzle_files_follow_dialect_and_language() {
+        let mut mem = MemoryFs::new();
+        mem.create_dir_all(Path::new("app")).unwrap();
+        let mut output = OutputDir::with_fs(Path::new("app"), WriteMode::Create, &mut mem);
+        write_orm_files(&mut output, Database::Mysql, Orm::Drizzle, false).unwrap();
+        drop(output);
+
+        let config = mem.read_to_string(Path::new("app/drizzle.config.js")).unwrap();
+        assert!(config.contains("dialect: \"mysql\""), "{config}");
+        assert!(config.contains("./src/db/schema.js"), "{config}");
+        assert!(!config.contains("DATABASE_URL!"), "{config}");
+        let schema = mem.read_to_string(Path::new("app/src/db/schema.js")).unwrap();
+        assert!(schema.contains("mysqlTable"), "{schema}");
+    }
+
+    #[test]
+    fn template_files_are_kept() {
+        let mut mem = MemoryFs::new();
+        mem.create_dir_all(Path::new("app/src/db")).unwrap();
+        mem.write(Path::new("app/src/db/types.ts"), b"// ours\n").unwrap();
+        let mut output = OutputDir::with_fs(Path::new("app"), WriteMode::Create, &mut mem);
+        write_orm_files(&mut output, Database::Postgres, Orm::Kysely, true).unwrap();
+        drop(output);
+
+        assert_eq!(mem.read_to_string(Path::new("app/src/db/types.ts")).unwrap(), "// ours\n");
+        assert!(mem.exists(Path::new("app/kysely.config.ts")));
+    }
+
+    #[test]
+    fn kysely_module_types_only_in_typescript() {
+        let ts = Orm::Kysely.db_module(Database::Sqlite, true).unwrap();
+        assert!(ts.contains("new Kysely<DB>"), "{ts}");
+        assert!(ts.contains("SqliteDialect"), "{ts}");
+        let js = Orm::Kysely.db_module(Database::Sqlite, false).unwrap();
+        assert!(!js.contains("DB"), "{js}");
+        assert!(Orm::Raw.db_module(Database::Postgres, true).is_none());
+    }
+}
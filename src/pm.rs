@@ -0,0 +1,234 @@
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::manifest::Manifest;
+
+/// JavaScript package manager used to install and run a project.
+///
+/// Every JS invocation (`install`, `add`, `run <script>`) goes through here so
+/// init and the runner agree on which tool to call.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum PackageManager {
+    #[default]
+    Bun,
+    Npm,
+    Pnpm,
+    Yarn,
+}
+
+impl PackageManager {
+    pub const ALL: [PackageManager; 4] = [
+        PackageManager::Bun,
+        PackageManager::Npm,
+        PackageManager::Pnpm,
+        PackageManager::Yarn,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PackageManager::Bun => "bun",
+            PackageManager::Npm => "npm",
+            PackageManager::Pnpm => "pnpm",
+            PackageManager::Yarn => "yarn",
+        }
+    }
+
+    /// The executable to spawn.
+    pub fn program(&self) -> &'static str {
+        self.name()
+    }
+
+    pub fn lockfiles(&self) -> &'static [&'static str] {
+        match self {
+            PackageManager::Bun => &["bun.lockb", "bun.lock"],
+            PackageManager::Npm => &["package-lock.json"],
+            PackageManager::Pnpm => &["pnpm-lock.yaml"],
+            PackageManager::Yarn => &["yarn.lock"],
+        }
+    }
+
+    /// Lockfiles belonging to the other package managers. Templates may ship
+    /// one; it must not be copied into a project that uses a different tool.
+    pub fn foreign_lockfiles(&self) -> Vec<&'static str> {
+        Self::ALL
+            .iter()
+            .filter(|pm| *pm != self)
+            .flat_map(|pm| pm.lockfiles().iter().copied())
+            .collect()
+    }
+
+    pub fn install_args(&self) -> Vec<String> {
+        vec!["install".to_string()]
+    }
+
+    /// Install exactly what the lockfile in `dir` records, failing if it is
+    /// out of date.
+    pub fn frozen_install_args(&self, dir: &Path) -> Vec<String> {
+        let args: &[&str] = match self {
+            PackageManager::Npm => &["ci"],
+            // Yarn 2+ dropped --frozen-lockfile for --immutable
+            PackageManager::Yarn if is_yarn_berry(dir) => &["install", "--immutable"],
+            PackageManager::Bun | PackageManager::Pnpm | PackageManager::Yarn => {
+                &["install", "--frozen-lockfile"]
+            }
+        };
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    pub fn add_args(&self, packages: &[&str]) -> Vec<String> {
+        let verb = match self {
+            PackageManager::Npm => "install",
+            PackageManager::Bun | PackageManager::Pnpm | PackageManager::Yarn => "add",
+        };
+        std::iter::once(verb)
+            .chain(packages.iter().copied())
+            .map(str::to_string)
+            .collect()
+    }
+
+    pub fn run_args(&self, script: &str) -> Vec<String> {
+        vec!["run".to_string(), script.to_string()]
+    }
+
+    /// `run_args` plus arguments for the script itself, which npm only
+    /// passes on after `--`.
+    pub fn run_args_with(&self, script: &str, extra: &[String]) -> Vec<String> {
+        let mut args = self.run_args(script);
+        if !extra.is_empty() && *self == PackageManager::Npm {
+            args.push("--".to_string());
+        }
+        args.extend(extra.iter().cloned());
+        args
+    }
+
+    /// Human-readable form of `run_args`, for next-step hints.
+    pub fn run_hint(&self, script: &str) -> String {
+        format!("{} {}", self.program(), self.run_args(script).join(" "))
+    }
+
+    /// Detect the package manager from lockfiles in `dir`.
+    pub fn detect(dir: &Path) -> Option<PackageManager> {
+        Self::ALL
+            .into_iter()
+            .find(|pm| pm.lockfiles().iter().any(|lock| dir.join(lock).exists()))
+    }
+
+    /// Package manager for a JS project in `js_dir` whose nullslate.toml lives
+    /// in `root`: the recorded choice wins, then lockfiles, then bun.
+    pub fn resolve(root: &Path, js_dir: &Path) -> PackageManager {
+        Manifest::load(root)
+            .ok()
+            .flatten()
+            .and_then(|m| m.project.package_manager)
+            .and_then(|name| name.parse().ok())
+            .or_else(|| Self::detect(js_dir))
+            .unwrap_or_default()
+    }
+}
+
+/// Yarn 2+ projects carry a `.yarnrc.yml` and a YAML lockfile with a
+/// `__metadata` block; Yarn 1 has neither.
+fn is_yarn_berry(dir: &Path) -> bool {
+    dir.join(".yarnrc.yml").exists()
+        || std::fs::read_to_string(dir.join("yarn.lock")).is_ok_and(|lock| lock.contains("__metadata:"))
+}
+
+impl fmt::Display for PackageManager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for PackageManager {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|pm| pm.name() == s)
+            .ok_or_else(|| anyhow::anyhow!("Unknown package manager '{s}' (expected bun, npm, pnpm or yarn)"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn detect_from_lockfiles() {
+        let cases = [
+            ("bun.lockb", PackageManager::Bun),
+            ("bun.lock", PackageManager::Bun),
+            ("pnpm-lock.yaml", PackageManager::Pnpm),
+            ("package-lock.json", PackageManager::Npm),
+            ("yarn.lock", PackageManager::Yarn),
+        ];
+        for (lockfile, expected) in cases {
+            let dir = tempdir().unwrap();
+            fs::write(dir.path().join(lockfile), "").unwrap();
+            assert_eq!(PackageManager::detect(dir.path()), Some(expected), "{lockfile}");
+        }
+    }
+
+    #[test]
+    fn detect_without_lockfile() {
+        let dir = tempdir().unwrap();
+        assert_eq!(PackageManager::detect(dir.path()), None);
+        assert_eq!(PackageManager::resolve(dir.path(), dir.path()), PackageManager::Bun);
+    }
+
+    #[test]
+    fn manifest_choice_beats_lockfile() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("yarn.lock"), "").unwrap();
+        fs::write(
+            dir.path().join("nullslate.toml"),
+            "[project]\npackage_manager = \"pnpm\"\n",
+        )
+        .unwrap();
+        assert_eq!(PackageManager::resolve(dir.path(), dir.path()), PackageManager::Pnpm);
+    }
+
+    #[test]
+    fn add_args_per_manager() {
+        assert_eq!(PackageManager::Npm.add_args(&["react"]), ["install", "react"]);
+        assert_eq!(PackageManager::Pnpm.add_args(&["react"]), ["add", "react"]);
+    }
+
+    #[test]
+    fn run_args_pass_script_arguments() {
+        let extra = ["--port".to_string(), "3001".to_string()];
+        assert_eq!(PackageManager::Npm.run_args_with("dev", &extra), ["run", "dev", "--", "--port", "3001"]);
+        assert_eq!(PackageManager::Bun.run_args_with("dev", &extra), ["run", "dev", "--port", "3001"]);
+        assert_eq!(PackageManager::Npm.run_args_with("dev", &[]), ["run", "dev"]);
+    }
+
+    #[test]
+    fn frozen_install_per_manager() {
+        let dir = tempfile::tempdir().unwrap();
+        let yarn = PackageManager::Yarn;
+        assert_eq!(PackageManager::Npm.frozen_install_args(dir.path()), ["ci"]);
+        assert_eq!(PackageManager::Bun.frozen_install_args(dir.path()), ["install", "--frozen-lockfile"]);
+        assert_eq!(yarn.frozen_install_args(dir.path()), ["install", "--frozen-lockfile"]);
+
+        std::fs::write(dir.path().join("yarn.lock"), "__metadata:\n  version: 8\n").unwrap();
+        assert_eq!(yarn.frozen_install_args(dir.path()), ["install", "--immutable"]);
+    }
+
+    #[test]
+    fn foreign_lockfiles_exclude_own() {
+        let foreign = PackageManager::Pnpm.foreign_lockfiles();
+        assert!(!foreign.contains(&"pnpm-lock.yaml"));
+        assert!(foreign.contains(&"bun.lockb"));
+        assert!(foreign.contains(&"yarn.lock"));
+    }
+
+    #[test]
+    fn parse_names() {
+        assert_eq!("yarn".parse::<PackageManager>().unwrap(), PackageManager::Yarn);
+        assert!("deno".parse::<PackageManager>().is_err());
+    }
+}
@@ -1,7 +1,20 @@
 use anyhow::{Context, Result};
-use std::env;
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Read};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, ExitStatus, Stdio};
+use std::thread;
+
+use crate::cache::TaskCache;
+use crate::env::{self, BUILD_MODE, DEV_MODE, SHELL_SOURCE};
+use crate::database::find_api_crate;
+use crate::devforge::{Devforge, Service, DEVFORGE_FILE};
+use crate::events::{self, Event, Stream};
+use crate::manifest::{Manifest, Profile, Task};
+use crate::pm::PackageManager;
+use crate::supervisor::{self, ProcessFailed, Proc, TaskRun};
+use crate::tasks::{self, Action, BUILTIN_TASKS};
+use crate::vfs::RealFs;
 
 #[derive(Debug, PartialEq)]
 pub(crate) enum ProjectKind {
@@ -14,7 +27,7 @@ pub(crate) enum ProjectKind {
 pub(crate) fn detect_project(start: &Path) -> Result<(PathBuf, ProjectKind)> {
     let mut dir = start.to_path_buf();
     loop {
-        if dir.join("devforge.toml").exists() {
+        if dir.join(DEVFORGE_FILE).exists() {
             return Ok((dir, ProjectKind::Fullstack));
         }
         if dir.join("package.json").exists() {
@@ -31,65 +44,656 @@ pub(crate) fn detect_project(start: &Path) -> Result<(PathBuf, ProjectKind)> {
     }
 }
 
-pub fn cmd_dev() -> Result<()> {
-    let cwd = env::current_dir().context("failed to get current directory")?;
+/// Flags shared by `ns dev`, `ns build` and `ns run`.
+#[derive(Debug, Clone)]
+pub struct RunOptions {
+    /// Env files to load; each command has its own default.
+    pub mode: Option<String>,
+    /// Skip tasks whose inputs didn't change since a cached run.
+    pub cache: bool,
+    /// How many tasks may run at once; unlimited when `None`.
+    pub jobs: Option<usize>,
+    /// A `[profiles.<name>]` section of nullslate.toml.
+    pub profile: Option<String>,
+    /// Cargo target triple, over the profile's.
+    pub target: Option<String>,
+    /// Passed on to the command that is run, e.g. the dev server.
+    pub args: Vec<String>,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        RunOptions {
+            mode: None,
+            cache: true,
+            jobs: None,
+            profile: None,
+            target: None,
+            args: Vec::new(),
+        }
+    }
+}
+
+impl RunOptions {
+    fn with_default_mode(&self, mode: &str) -> RunOptions {
+        RunOptions {
+            mode: Some(self.mode.clone().unwrap_or_else(|| mode.to_string())),
+            ..self.clone()
+        }
+    }
+}
+
+/// Start the project's dev server with the env files for the mode
+/// (development by default), or the `dev` task if nullslate.toml has one.
+pub fn cmd_dev(options: &RunOptions) -> Result<()> {
+    let cwd = std::env::current_dir().context("failed to get current directory")?;
     let (root, kind) = detect_project(&cwd)?;
+    let options = options.with_default_mode(DEV_MODE);
+    if load_tasks(&root)?.contains_key("dev") {
+        return run_task(&root, &kind, "dev", &options);
+    }
+    let mode = options.mode.as_deref().unwrap_or(DEV_MODE);
+    let profile = load_profile(&root, &options)?;
 
     match kind {
         ProjectKind::Fullstack => {
-            run_cmd(&root, "cargo", &["xtask", "dev"])?;
+            dev_fullstack(&root, mode, &profile, &options.args)?;
         }
         ProjectKind::Frontend => {
-            run_cmd(&root, "bun", &["dev"])?;
+            let step = Step::script(&root, &root, "dev", &web_args(&profile, &options.args));
+            run_cmd(&root, &root, mode, &step.program, &step.args, &profile.env)?;
         }
         ProjectKind::Rust => {
-            run_cmd(&root, "cargo", &["run"])?;
+            let args = cargo_args(&["run"], &profile, &program_args(&options.args));
+            run_cmd(&root, &root, mode, "cargo", &args, &profile.env)?;
         }
     }
     Ok(())
 }
 
-pub fn cmd_build() -> Result<()> {
-    let cwd = env::current_dir().context("failed to get current directory")?;
+/// Build the project with the env files for the mode (production by
+/// default), or run the `build` task if nullslate.toml has one. The Rust and
+/// web builds of a fullstack project run side by side.
+pub fn cmd_build(options: &RunOptions) -> Result<()> {
+    let cwd = std::env::current_dir().context("failed to get current directory")?;
+    let (root, kind) = detect_project(&cwd)?;
+    run_task(&root, &kind, "build", &options.with_default_mode(BUILD_MODE))
+}
+
+/// Run a task from nullslate.toml and the tasks it depends on, or list the
+/// tasks when none is given. `dev` and `build` fall back to the built-ins.
+pub fn cmd_run(task: Option<&str>, options: &RunOptions) -> Result<()> {
+    let cwd = std::env::current_dir().context("failed to get current directory")?;
     let (root, kind) = detect_project(&cwd)?;
+    let tasks = load_tasks(&root)?;
+
+    let Some(task) = task else {
+        if events::is_jsonl() {
+            for (name, task) in &tasks {
+                events::emit(Event::TaskListed {
+                    task: name.clone(),
+                    commands: task.commands.clone(),
+                    depends_on: task.depends_on.clone(),
+                    builtin: false,
+                });
+            }
+            for builtin in BUILTIN_TASKS.iter().filter(|b| !tasks.contains_key(**b)) {
+                events::emit(Event::TaskListed {
+                    task: builtin.to_string(),
+                    commands: Vec::new(),
+                    depends_on: Vec::new(),
+                    builtin: true,
+                });
+            }
+            return Ok(());
+        }
+        for (name, task) in &tasks {
+            if task.commands.is_empty() {
+                println!("{name:20} (runs {})", task.depends_on.join(", "));
+            } else {
+                println!("{name:20} {}", task.commands.join(" && "));
+            }
+        }
+        for builtin in BUILTIN_TASKS.iter().filter(|b| !tasks.contains_key(**b)) {
+            println!("{builtin:20} (built-in)");
+        }
+        return Ok(());
+    };
+    match task {
+        "dev" if !tasks.contains_key("dev") => cmd_dev(options),
+        "build" if !tasks.contains_key("build") => cmd_build(options),
+        _ => run_task(&root, &kind, task, options),
+    }
+}
+
+fn load_tasks(root: &Path) -> Result<BTreeMap<String, Task>> {
+    Ok(Manifest::load(root)?.map(|m| m.tasks).unwrap_or_default())
+}
+
+/// The profile named by `--profile`, or an empty one, with `--target` over
+/// its target.
+fn load_profile(root: &Path, options: &RunOptions) -> Result<Profile> {
+    let mut profile = named_profile(root, options.profile.as_deref())?;
+    if let Some(target) = &options.target {
+        profile.target = Some(target.clone());
+    }
+    Ok(profile)
+}
+
+fn named_profile(root: &Path, name: Option<&str>) -> Result<Profile> {
+    let Some(name) = name else {
+        return Ok(Profile::default());
+    };
+    let profiles = Manifest::load(root)?.map(|m| m.profiles).unwrap_or_default();
+    if let Some(profile) = profiles.get(name) {
+        return Ok(profile.clone());
+    }
+    let defined: Vec<&str> = profiles.keys().map(String::as_str).collect();
+    if defined.is_empty() {
+        anyhow::bail!("Unknown profile {name:?}; nullslate.toml defines no profiles");
+    }
+    anyhow::bail!("Unknown profile {name:?}; nullslate.toml defines {}", defined.join(", "))
+}
+
+/// Run `name` and its dependencies with the env files for the mode. It
+/// defaults to production for the built-in build and when `name` is
+/// `build`, and to development otherwise. The arguments go to `name` only,
+/// after its last command.
+fn run_task(root: &Path, kind: &ProjectKind, name: &str, options: &RunOptions) -> Result<()> {
+    let mode = options.mode.as_deref().unwrap_or(default_mode(name));
+    let cache = options.cache;
+    let profile = load_profile(root, options)?;
+    let tasks = load_tasks(root)?;
+    let mut runs = Vec::new();
+    for planned in tasks::plan(&tasks, name)? {
+        let args: &[String] = if planned.name == name { &options.args } else { &[] };
+        match planned.action {
+            Action::Commands(task) => {
+                if !args.is_empty() && task.commands.is_empty() {
+                    anyhow::bail!("{name} has no commands to pass arguments to");
+                }
+                let env: Vec<(String, String)> = profile.env.iter().chain(&task.env).map(|(k, v)| (k.clone(), v.clone())).collect();
+                let steps = task
+                    .commands
+                    .iter()
+                    .enumerate()
+                    .map(|(i, command)| {
+                        let command = if i + 1 == task.commands.len() && !args.is_empty() {
+                            format!("{command} {}", shell_words(args))
+                        } else {
+                            command.clone()
+                        };
+                        shell_proc(root, mode, &planned.name, &command, task.cwd.as_deref(), env.clone())
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let task_cache = (cache && !task.inputs.is_empty()).then(|| TaskCache {
+                    root: root.to_path_buf(),
+                    base: root.join(task.cwd.as_deref().unwrap_or(Path::new(""))),
+                    inputs: task.inputs.clone(),
+                    outputs: task.outputs.clone(),
+                });
+                runs.push(TaskRun {
+                    name: planned.name,
+                    steps,
+                    depends_on: planned.depends_on,
+                    cache: task_cache,
+                });
+            }
+            Action::Build => {
+                // Each part is a task of its own, so each is cached on its own
+                // and the parts run side by side
+                let mut parts = Vec::new();
+                for part in build_tasks(root, kind, &profile, args) {
+                    let mut envs = child_env(root, &part.step.dir, options.mode.as_deref().unwrap_or(BUILD_MODE))?;
+                    envs.extend(profile.env.clone());
+                    let proc = Proc {
+                        name: part.name.clone(),
+                        envs,
+                        program: part.step.program,
+                        args: part.step.args,
+                        cwd: part.step.dir,
+                        ports: Vec::new(),
+                        ready: None,
+                    };
+                    runs.push(TaskRun {
+                        name: part.name.clone(),
+                        steps: vec![proc],
+                        depends_on: Vec::new(),
+                        cache: part.cache.filter(|_| cache),
+                    });
+                    parts.push(part.name);
+                }
+                if parts != [planned.name.as_str()] {
+                    runs.push(TaskRun {
+                        name: planned.name,
+                        steps: Vec::new(),
+                        depends_on: parts,
+                        cache: None,
+                    });
+                }
+            }
+        }
+    }
+    supervisor::run_tasks(runs, options.jobs)
+}
+
+/// Mode for a run of `task` without `--mode`.
+fn default_mode(task: &str) -> &'static str {
+    if task == "build" {
+        BUILD_MODE
+    } else {
+        DEV_MODE
+    }
+}
+
+/// One command of a built-in.
+struct Step {
+    dir: PathBuf,
+    program: String,
+    args: Vec<String>,
+}
+
+impl Step {
+    fn new(dir: &Path, program: &str, args: Vec<String>) -> Step {
+        Step {
+            dir: dir.to_path_buf(),
+            program: program.to_string(),
+            args,
+        }
+    }
+
+    /// A package.json script, with `extra` passed on to it.
+    fn script(root: &Path, dir: &Path, script: &str, extra: &[String]) -> Step {
+        let pm = PackageManager::resolve(root, dir);
+        Step {
+            dir: dir.to_path_buf(),
+            program: pm.program().to_string(),
+            args: pm.run_args_with(script, extra),
+        }
+    }
+}
+
+/// `cargo <command>` with the profile's features and target, then `extra`.
+fn cargo_args(command: &[&str], profile: &Profile, extra: &[String]) -> Vec<String> {
+    let mut args: Vec<String> = command.iter().map(|a| a.to_string()).collect();
+    if !profile.features.is_empty() {
+        args.push("--features".to_string());
+        args.push(profile.features.join(","));
+    }
+    if let Some(target) = &profile.target {
+        args.push("--target".to_string());
+        args.push(target.clone());
+    }
+    args.extend(extra.iter().cloned());
+    args
+}
+
+/// Arguments for a web script: the profile's mode, then `extra`.
+fn web_args(profile: &Profile, extra: &[String]) -> Vec<String> {
+    let mut args = Vec::new();
+    if let Some(mode) = &profile.web_mode {
+        args.push("--mode".to_string());
+        args.push(mode.clone());
+    }
+    args.extend(extra.iter().cloned());
+    args
+}
+
+/// Arguments for the program `cargo run` starts rather than for cargo.
+fn program_args(args: &[String]) -> Vec<String> {
+    if args.is_empty() {
+        return Vec::new();
+    }
+    std::iter::once("--".to_string()).chain(args.iter().cloned()).collect()
+}
+
+/// `args` quoted for the shell that runs task commands.
+fn shell_words(args: &[String]) -> String {
+    let quote = |arg: &String| {
+        let plain = !arg.is_empty()
+            && arg.chars().all(|c| c.is_ascii_alphanumeric() || "-_./=:,@+%".contains(c));
+        if plain {
+            arg.clone()
+        } else if cfg!(windows) {
+            format!("\"{}\"", arg.replace('"', "\\\""))
+        } else {
+            format!("'{}'", arg.replace('\'', "'\\''"))
+        }
+    };
+    args.iter().map(quote).collect::<Vec<_>>().join(" ")
+}
+
+/// A part of the built-in build, with what it reads and writes when known.
+struct BuildTask {
+    name: String,
+    step: Step,
+    cache: Option<TaskCache>,
+}
+
+impl BuildTask {
+    /// A release build of the crate in `dir`.
+    fn cargo(root: &Path, dir: &Path, name: &str, profile: &Profile, extra: &[String]) -> BuildTask {
+        BuildTask {
+            name: name.to_string(),
+            step: Step::new(dir, "cargo", cargo_args(&["build", "--release"], profile, extra)),
+            // Extra arguments may move the binary, e.g. `--target`
+            cache: if extra.is_empty() { cargo_cache(root, dir, profile.target.as_deref()) } else { None },
+        }
+    }
 
+    fn web(root: &Path, dir: &Path, name: &str, profile: &Profile, extra: &[String]) -> BuildTask {
+        BuildTask {
+            name: name.to_string(),
+            step: Step::script(root, dir, "build", &web_args(profile, extra)),
+            cache: Some(TaskCache {
+                root: root.to_path_buf(),
+                base: dir.to_path_buf(),
+                // Everything but the bundler's own caches; outputs are
+                // never inputs
+                inputs: ["**/*", "!.vinxi", "!.tanstack", "!.nitro"].map(String::from).to_vec(),
+                outputs: ["dist", ".output"].map(String::from).to_vec(),
+            }),
+        }
+    }
+}
+
+/// The built-in build: release builds of the Rust code and the web app's
+/// `build` script. `extra` goes to cargo when there is Rust code.
+fn build_tasks(root: &Path, kind: &ProjectKind, profile: &Profile, extra: &[String]) -> Vec<BuildTask> {
     match kind {
         ProjectKind::Fullstack => {
-            run_cmd(&root, "cargo", &["build", "--release"])?;
+            let mut parts = vec![BuildTask::cargo(root, &api_dir(root), "build:api", profile, extra)];
             let web_dir = root.join("web");
             if web_dir.exists() {
-                run_cmd(&web_dir, "bun", &["run", "build"])?;
+                parts.push(BuildTask::web(root, &web_dir, "build:web", profile, &[]));
             }
+            parts
         }
-        ProjectKind::Frontend => {
-            run_cmd(&root, "bun", &["run", "build"])?;
+        ProjectKind::Frontend => vec![BuildTask::web(root, root, "build", profile, extra)],
+        ProjectKind::Rust => vec![BuildTask::cargo(root, root, "build", profile, extra)],
+    }
+}
+
+/// The API crate of a fullstack project, where `cargo run` and `cargo
+/// build` go so the profile's features apply to it.
+fn api_dir(root: &Path) -> PathBuf {
+    find_api_crate(&mut RealFs, root)
+        .map(|dir| root.join(dir))
+        .unwrap_or_else(|| root.to_path_buf())
+}
+
+/// Caching for `cargo build --release` in `dir`, whose outputs are the
+/// binaries of that crate as `cargo metadata` reports them. Not cached when
+/// cargo can't describe the workspace or it reaches outside the project.
+fn cargo_cache(root: &Path, dir: &Path, target: Option<&str>) -> Option<TaskCache> {
+    let output = Command::new("cargo")
+        .args(["metadata", "--no-deps", "--format-version", "1", "--manifest-path"])
+        .arg(dir.join("Cargo.toml"))
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let metadata: serde_json::Value = serde_json::from_slice(&output.stdout).ok()?;
+    let relative = |path: &Path| -> Option<String> {
+        let path = path.strip_prefix(root).ok()?.to_string_lossy().replace('\\', "/");
+        Some(if path.is_empty() { "**".to_string() } else { path })
+    };
+    let target_dir = relative(Path::new(metadata.get("target_directory")?.as_str()?))?;
+    let mut inputs = Vec::new();
+    let mut outputs = Vec::new();
+    for package in metadata.get("packages")?.as_array()? {
+        let package_dir = Path::new(package.get("manifest_path")?.as_str()?).parent()?;
+        // Everything in the package, so build scripts and included files count
+        inputs.push(relative(package_dir)?);
+        if package_dir != dir {
+            continue;
         }
-        ProjectKind::Rust => {
-            run_cmd(&root, "cargo", &["build", "--release"])?;
+        for bin in package.get("targets")?.as_array()? {
+            let is_bin = bin.get("kind")?.as_array()?.iter().any(|kind| kind == "bin");
+            if is_bin {
+                outputs.push(format!(
+                    "{target_dir}/{}release/{}{}",
+                    target.map(|t| format!("{t}/")).unwrap_or_default(),
+                    bin.get("name")?.as_str()?,
+                    std::env::consts::EXE_SUFFIX
+                ));
+            }
         }
     }
-    Ok(())
+    if outputs.is_empty() {
+        return None;
+    }
+    inputs.extend(
+        ["Cargo.lock", ".cargo/config.toml", ".cargo/config", "rust-toolchain", "rust-toolchain.toml"].map(String::from),
+    );
+    Some(TaskCache {
+        root: root.to_path_buf(),
+        base: root.to_path_buf(),
+        inputs,
+        outputs,
+    })
 }
 
-fn run_cmd(dir: &Path, program: &str, args: &[&str]) -> Result<()> {
-    let status = Command::new(program)
-        .args(args)
-        .current_dir(dir)
-        .status()
-        .with_context(|| format!("failed to run {program}"))?;
+/// Run the services declared in devforge.toml, or else the Rust API and the
+/// web dev server, side by side, stopping all of them as soon as one exits.
+/// `args` go to the API.
+fn dev_fullstack(root: &Path, mode: &str, profile: &Profile, args: &[String]) -> Result<()> {
+    let services = match Devforge::load(root)? {
+        Some(config) => {
+            for key in &config.unknown_keys {
+                warn(&format!("Unknown key {key} in {DEVFORGE_FILE}"));
+            }
+            config.services
+        }
+        None => Vec::new(),
+    };
+    if !services.is_empty() {
+        if !args.is_empty() {
+            anyhow::bail!("Arguments can't be passed to {DEVFORGE_FILE} services; add them to the service's command");
+        }
+        let procs = services
+            .into_iter()
+            .map(|service| service_proc(root, mode, profile, service))
+            .collect::<Result<Vec<_>>>()?;
+        return supervisor::run_all(procs);
+    }
+
+    let mut envs = child_env(root, root, mode)?;
+    envs.extend(profile.env.clone());
+    let mut procs = vec![Proc {
+        name: "api".to_string(),
+        program: "cargo".to_string(),
+        args: cargo_args(&["run"], profile, &program_args(args)),
+        cwd: api_dir(root),
+        envs,
+        ports: Vec::new(),
+        ready: None,
+    }];
+
+    let web_dir = root.join("web");
+    if web_dir.join("package.json").exists() {
+        let step = Step::script(root, &web_dir, "dev", &web_args(profile, &[]));
+        let mut envs = child_env(root, &web_dir, mode)?;
+        envs.extend(profile.env.clone());
+        procs.push(Proc {
+            name: "web".to_string(),
+            program: step.program,
+            args: step.args,
+            envs,
+            cwd: web_dir,
+            ports: Vec::new(),
+            ready: None,
+        });
+    }
+    supervisor::run_all(procs)
+}
+
+/// A devforge service as a process, with the profile's env under its own.
+fn service_proc(root: &Path, mode: &str, profile: &Profile, service: Service) -> Result<Proc> {
+    let env = profile.env.clone().into_iter().chain(service.env).collect();
+    let mut proc = shell_proc(root, mode, &service.name, &service.command, service.cwd.as_deref(), env)?;
+    proc.ports = service.ports;
+    proc.ready = service.ready;
+    Ok(proc)
+}
+
+/// `command` run through the shell in `cwd` (relative to the root), with
+/// the env files of that directory, or of the root if it has none, and
+/// `extra_env` on top.
+fn shell_proc(
+    root: &Path,
+    mode: &str,
+    name: &str,
+    command: &str,
+    cwd: Option<&Path>,
+    extra_env: Vec<(String, String)>,
+) -> Result<Proc> {
+    let cwd = match cwd {
+        Some(dir) => root.join(dir),
+        None => root.to_path_buf(),
+    };
+    if !cwd.is_dir() {
+        anyhow::bail!("{name} runs in {}, which doesn't exist", cwd.display());
+    }
+    let env_dir = if env::has_env_files(&cwd) { cwd.as_path() } else { root };
+    let mut envs = child_env(root, env_dir, mode)?;
+    envs.extend(extra_env);
+
+    let (program, flag) = if cfg!(windows) { ("cmd", "/C") } else { ("sh", "-c") };
+    Ok(Proc {
+        name: name.to_string(),
+        program: program.to_string(),
+        args: vec![flag.to_string(), command.to_string()],
+        cwd,
+        envs,
+        ports: Vec::new(),
+        ready: None,
+    })
+}
+
+fn warn(message: &str) {
+    events::emit(Event::Warning {
+        message: message.to_string(),
+    });
+}
+
+/// Variables from the env files in `dir` for a child running there. The
+/// child inherits everything already set in our environment anyway.
+fn child_env(root: &Path, dir: &Path, mode: &str) -> Result<Vec<(String, String)>> {
+    Ok(env::load(&RealFs, root, dir, mode)?
+        .into_iter()
+        .filter(|var| var.source != SHELL_SOURCE)
+        .map(|var| (var.key, var.value))
+        .collect())
+}
+
+/// Run `program` in the foreground with the env files of `dir` and
+/// `extra_env` on top.
+fn run_cmd(
+    root: &Path,
+    dir: &Path,
+    mode: &str,
+    program: &str,
+    args: &[String],
+    extra_env: &BTreeMap<String, String>,
+) -> Result<()> {
+    let mut vars = child_env(root, dir, mode)?;
+    vars.extend(extra_env.clone());
+    events::emit(Event::ProcessStarted {
+        program: program.to_string(),
+        args: args.to_vec(),
+        cwd: dir.to_path_buf(),
+    });
+
+    let mut command = Command::new(program);
+    command.args(args).current_dir(dir).envs(vars);
+    let status = if events::is_jsonl() {
+        run_captured(command, program)
+    } else {
+        command.status()
+    }
+    .with_context(|| format!("failed to run {program}"))?;
+
+    events::emit(Event::ProcessExited {
+        program: program.to_string(),
+        code: status.code(),
+    });
 
     if !status.success() {
-        anyhow::bail!("{program} exited with {status}");
+        return Err(ProcessFailed::new(program, status).into());
     }
     Ok(())
 }
 
+/// Run with piped output, re-emitting every line as a `process_output` event
+/// so the JSON stream stays parseable.
+fn run_captured(mut command: Command, program: &str) -> std::io::Result<ExitStatus> {
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let readers = [
+        child.stdout.take().map(|out| forward_lines(out, program, Stream::Stdout)),
+        child.stderr.take().map(|err| forward_lines(err, program, Stream::Stderr)),
+    ];
+
+    let status = child.wait()?;
+    for reader in readers.into_iter().flatten() {
+        let _ = reader.join();
+    }
+    Ok(status)
+}
+
+fn forward_lines(
+    pipe: impl Read + Send + 'static,
+    program: &str,
+    stream: Stream,
+) -> thread::JoinHandle<()> {
+    let program = program.to_string();
+    thread::spawn(move || {
+        for line in BufReader::new(pipe).lines().map_while(|l| l.ok()) {
+            events::emit(Event::ProcessOutput {
+                program: program.clone(),
+                stream,
+                line,
+            });
+        }
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::fs;
     use tempfile::tempdir;
 
+    #[test]
+    fn cargo_cache_covers_every_binary_of_the_api_crate() {
+        let dir = tempdir().unwrap();
+        let api = dir.path().join("api");
+        fs::create_dir_all(api.join("src/bin")).unwrap();
+        fs::write(
+            api.join("Cargo.toml"),
+            "[package]\nname = \"api\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n\
+             [[bin]]\nname = \"server\"\npath = \"src/main.rs\"\n\n\
+             [[bin]]\nname = \"worker\"\npath = \"src/bin/worker.rs\"\n",
+        )
+        .unwrap();
+        fs::write(api.join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(api.join("src/bin/worker.rs"), "fn main() {}").unwrap();
+
+        let cache = cargo_cache(dir.path(), &api_dir(dir.path()), None).unwrap();
+        let exe = std::env::consts::EXE_SUFFIX;
+        assert_eq!(
+            cache.outputs,
+            [format!("api/target/release/server{exe}"), format!("api/target/release/worker{exe}")]
+        );
+        assert_eq!(cache.inputs[0], "api");
+    }
+
     #[test]
     fn detect_fullstack_project() {
         let dir = tempdir().unwrap();
@@ -138,6 +742,74 @@ mod tests {
         assert_eq!(kind, ProjectKind::Frontend);
     }
 
+    #[test]
+    fn profile_and_pass_through_arguments() {
+        let profile = Profile {
+            features: vec!["tracing".to_string(), "otel".to_string()],
+            target: Some("x86_64-unknown-linux-musl".to_string()),
+            web_mode: Some("staging".to_string()),
+            ..Default::default()
+        };
+        let port = ["--port".to_string(), "3001".to_string()];
+        assert_eq!(
+            cargo_args(&["run"], &profile, &program_args(&port)),
+            ["run", "--features", "tracing,otel", "--target", "x86_64-unknown-linux-musl", "--", "--port", "3001"]
+        );
+        assert_eq!(web_args(&profile, &port), ["--mode", "staging", "--port", "3001"]);
+        assert_eq!(cargo_args(&["run"], &Profile::default(), &program_args(&[])), ["run"]);
+        #[cfg(unix)]
+        assert_eq!(
+            shell_words(&["--grep".to_string(), "it's ok".to_string(), "a=b".to_string()]),
+            "--grep 'it'\\''s ok' a=b"
+        );
+    }
+
+    #[test]
+    fn unknown_profile_lists_the_defined_ones() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("nullslate.toml"), "[profiles.staging]\n[profiles.prod]\n").unwrap();
+        assert!(named_profile(dir.path(), Some("staging")).is_ok());
+        let err = named_profile(dir.path(), Some("stage")).unwrap_err().to_string();
+        assert_eq!(err, "Unknown profile \"stage\"; nullslate.toml defines prod, staging");
+    }
+
+    #[test]
+    fn target_flag_overrides_the_profile() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("nullslate.toml"), "[profiles.staging]\ntarget = \"a\"\n").unwrap();
+        let options = RunOptions {
+            profile: Some("staging".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(load_profile(dir.path(), &options).unwrap().target.as_deref(), Some("a"));
+        let options = RunOptions {
+            target: Some("b".to_string()),
+            ..options
+        };
+        assert_eq!(load_profile(dir.path(), &options).unwrap().target.as_deref(), Some("b"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn build_tasks_default_to_production() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        fs::write(root.join("package.json"), "{}").unwrap();
+        fs::write(root.join(".env.production"), "NS_T_RUN_MODE=prod\n").unwrap();
+        fs::write(root.join(".env.development"), "NS_T_RUN_MODE=dev\n").unwrap();
+        fs::write(
+            root.join("nullslate.toml"),
+            "[tasks.build]\ncommands = [\"printf %s $NS_T_RUN_MODE > build.out\"]\n\n\
+             [tasks.lint]\ncommands = [\"printf %s $NS_T_RUN_MODE > lint.out\"]\n",
+        )
+        .unwrap();
+        let (root, kind) = detect_project(root).unwrap();
+        run_task(&root, &kind, "build", &RunOptions::default()).unwrap();
+        run_task(&root, &kind, "lint", &RunOptions::default()).unwrap();
+        assert_eq!(fs::read_to_string(root.join("build.out")).unwrap(), "prod");
+        assert_eq!(fs::read_to_string(root.join("lint.out")).unwrap(), "dev");
+    }
+
     #[test]
     fn detect_no_project_fails() {
         let dir = tempdir().unwrap();
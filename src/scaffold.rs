@@ -0,0 +1,1340 @@
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::auth::{dev_origin, provider_skips, write_provider_files, AuthProvider};
+use crate::database::{db_module_variants, write_database_url, write_db_module, Database};
+use crate::env::write_env_examples;
+use crate::events::{Event, ProgressFn, Step};
+use crate::features::{
+    cleanup_layout_for_no_auth, generate_env_file, ROOT_LAYOUTS, get_files_to_skip, get_lib_files_to_skip,
+    set_package_name, update_auth_packages, update_lib_package_json, update_package_json,
+};
+use crate::fullstack::scaffold_fullstack;
+use crate::git::{init_repo, is_inside_git_work_tree, GitOptions, GitOutcome};
+use crate::language::{convert_package_json, rewrite_references, select_variants};
+use crate::manifest::{Manifest, ProjectSection, MANIFEST_FILE};
+use crate::naming::ProjectName;
+use crate::orm::{write_orm_files, Orm};
+use crate::pm::PackageManager;
+use crate::template::{
+    check_output_dir, clone_template, copy_template, default_template_url, install_deps,
+    template_root, CopyReport, OutputDir, TemplateVars, WriteMode,
+};
+use crate::versions::{
+    apply_pins, dependencies_changed, installed_versions, ResolvedPolicy, TemplateConfig, VersionPolicy,
+};
+use crate::vfs::{DiffEntry, FileSystem, MemoryFs, RealFs};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ProjectType {
+    App,
+    Fullstack,
+    Lib,
+}
+
+impl ProjectType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProjectType::App => "app",
+            ProjectType::Fullstack => "fullstack",
+            ProjectType::Lib => "lib",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Language {
+    #[default]
+    TypeScript,
+    JavaScript,
+}
+
+impl Language {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Language::TypeScript => "typescript",
+            Language::JavaScript => "javascript",
+        }
+    }
+}
+
+/// Optional pieces of the template to keep. App and fullstack projects use
+/// `auth`/`docs`/`db`; lib projects use `react`/`css`/`testing`. `lang`
+/// applies to every project type.
+#[derive(Clone, Debug, Default)]
+pub struct Features {
+    pub auth: bool,
+    /// Auth.js providers to set up; GitHub when empty.
+    pub auth_providers: Vec<AuthProvider>,
+    pub docs: bool,
+    pub db: Database,
+    /// Query layer for the database; app projects only.
+    pub orm: Orm,
+    pub lang: Language,
+    pub react: bool,
+    pub css: bool,
+    pub testing: bool,
+}
+
+impl Features {
+    /// The providers to set up, none when auth is off.
+    pub fn providers(&self) -> Vec<AuthProvider> {
+        if !self.auth {
+            Vec::new()
+        } else if self.auth_providers.is_empty() {
+            vec![AuthProvider::GitHub]
+        } else {
+            self.auth_providers.clone()
+        }
+    }
+}
+
+/// Where the template files come from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TemplateSource {
+    /// A git URL, shallow-cloned into a temporary directory.
+    Git(String),
+    /// A directory on disk, used in place.
+    Local(PathBuf),
+}
+
+impl fmt::Display for TemplateSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemplateSource::Git(url) => write!(f, "{url}"),
+            TemplateSource::Local(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// What a scaffold run did.
+#[derive(Debug)]
+pub struct ScaffoldReport {
+    pub project_name: String,
+    pub project_type: ProjectType,
+    pub output_path: PathBuf,
+    pub template: TemplateSource,
+    pub files: CopyReport,
+    pub git_initialized: bool,
+    pub git_committed: bool,
+    /// Why git init or the initial commit failed. The files are in place
+    /// regardless.
+    pub git_error: Option<String>,
+    pub installed: bool,
+    pub version_policy: ResolvedPolicy,
+    /// Versions of the managed packages found after install. Empty when
+    /// install was skipped or failed.
+    pub versions: BTreeMap<String, String>,
+    /// OAuth callback URLs to register with the picked auth providers.
+    pub auth_callbacks: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+/// A project rendered by [`Scaffolder::render_in_memory`].
+#[derive(Debug)]
+pub struct RenderedProject {
+    pub fs: MemoryFs,
+    pub output_path: PathBuf,
+    pub files: CopyReport,
+}
+
+impl RenderedProject {
+    /// Content hash of the rendered tree, independent of the output path.
+    pub fn hash(&self) -> String {
+        self.fs.hash(&self.output_path)
+    }
+
+    pub fn diff(&self) -> Vec<DiffEntry> {
+        self.fs.diff_against_disk()
+    }
+
+    pub fn write_to_disk(&self) -> Result<()> {
+        self.fs.flush_to(&mut RealFs)?;
+        Ok(())
+    }
+}
+
+/// Check the syntax of a project name. Scoped names (`@scope/name`) pass
+/// here; [`ProjectName::check_for`] limits them to lib projects.
+pub fn validate_project_name(name: &str) -> Result<()> {
+    ProjectName::parse(name).map(|_| ())
+}
+
+/// Programmatic entry point for scaffolding a project.
+///
+/// ```no_run
+/// use nullslate_cli::{Features, ProjectType, Scaffolder};
+///
+/// let report = Scaffolder::new("my-app")
+///     .project_type(ProjectType::App)
+///     .features(Features { auth: true, ..Default::default() })
+///     .install(false)
+///     .scaffold()?;
+/// println!("wrote {} files", report.files.written.len());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub struct Scaffolder<'a> {
+    project_name: String,
+    project_type: ProjectType,
+    features: Features,
+    template: Option<TemplateSource>,
+    variables: TemplateVars,
+    output_path: Option<PathBuf>,
+    write_mode: WriteMode,
+    package_manager: PackageManager,
+    versions: VersionPolicy,
+    git: bool,
+    git_options: GitOptions,
+    install: bool,
+    on_progress: Option<ProgressFn<'a>>,
+}
+
+impl<'a> Scaffolder<'a> {
+    pub fn new(project_name: impl Into<String>) -> Self {
+        Self {
+            project_name: project_name.into(),
+            project_type: ProjectType::App,
+            features: Features::default(),
+            template: None,
+            variables: TemplateVars::new(),
+            output_path: None,
+            write_mode: WriteMode::Create,
+            package_manager: PackageManager::default(),
+            versions: VersionPolicy::default(),
+            git: true,
+            git_options: GitOptions::default(),
+            install: true,
+            on_progress: None,
+        }
+    }
+
+    pub fn project_type(mut self, project_type: ProjectType) -> Self {
+        self.project_type = project_type;
+        self
+    }
+
+    pub fn features(mut self, features: Features) -> Self {
+        self.features = features;
+        self
+    }
+
+    /// Template to render. Defaults to the official template for the project type.
+    pub fn template(mut self, source: TemplateSource) -> Self {
+        self.template = Some(source);
+        self
+    }
+
+    /// Extra `{{key}}` placeholder to substitute in text files.
+    pub fn variable(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.variables.insert(key.into(), value.into());
+        self
+    }
+
+    /// Output directory. Defaults to `./<name>`, without the scope of a
+    /// scoped name.
+    pub fn output_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.output_path = Some(path.into());
+        self
+    }
+
+    pub fn write_mode(mut self, mode: WriteMode) -> Self {
+        self.write_mode = mode;
+        self
+    }
+
+    /// Package manager used for install and recorded in nullslate.toml.
+    pub fn package_manager(mut self, pm: PackageManager) -> Self {
+        self.package_manager = pm;
+        self
+    }
+
+    /// How dependency versions are chosen. Defaults to [`VersionPolicy::Auto`].
+    pub fn versions(mut self, policy: VersionPolicy) -> Self {
+        self.versions = policy;
+        self
+    }
+
+    pub fn git(mut self, enabled: bool) -> Self {
+        self.git = enabled;
+        self
+    }
+
+    /// Branch, author, commit and remote settings for the new repository.
+    pub fn git_options(mut self, options: GitOptions) -> Self {
+        self.git_options = options;
+        self
+    }
+
+    pub fn install(mut self, enabled: bool) -> Self {
+        self.install = enabled;
+        self
+    }
+
+    /// Called for every progress event, in order.
+    pub fn on_progress(mut self, callback: impl FnMut(&Event) + 'a) -> Self {
+        self.on_progress = Some(Box::new(callback));
+        self
+    }
+
+    fn emit(&mut self, event: Event) {
+        if let Some(callback) = self.on_progress.as_mut() {
+            callback(&event);
+        }
+    }
+
+    fn name(&self) -> Result<ProjectName> {
+        let name = ProjectName::parse(&self.project_name)?;
+        name.check_for(self.project_type)?;
+        Ok(name)
+    }
+
+    fn template_vars(&self) -> Result<TemplateVars> {
+        let mut vars = self.variables.clone();
+        vars.extend(self.name()?.template_vars());
+        Ok(vars)
+    }
+
+    fn resolved_output_path(&self, name: &ProjectName) -> PathBuf {
+        self.output_path
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(name.dir_name()))
+    }
+
+    fn resolved_template(&self) -> TemplateSource {
+        self.template
+            .clone()
+            .unwrap_or_else(|| TemplateSource::Git(default_template_url(self.project_type).to_string()))
+    }
+
+    /// Fetch the template into `temp_dir` (for git sources) and return the
+    /// directory holding its files.
+    fn fetch_template(&mut self, source: &TemplateSource, temp_dir: &Path) -> Result<PathBuf> {
+        self.emit(Event::StepStarted {
+            step: Step::FetchTemplate,
+        });
+        let template_path = match source {
+            TemplateSource::Git(url) => {
+                clone_template(url, temp_dir)?;
+                temp_dir.to_path_buf()
+            }
+            TemplateSource::Local(path) => {
+                if !path.is_dir() {
+                    anyhow::bail!("Template directory '{}' does not exist", path.display());
+                }
+                template_root(path)
+            }
+        };
+        self.emit(Event::StepFinished {
+            step: Step::FetchTemplate,
+        });
+        self.emit(Event::TemplateFetched {
+            url: source.to_string(),
+        });
+        Ok(template_path)
+    }
+
+    /// Directory holding the project's package.json.
+    fn js_dir(&self, root: &Path) -> PathBuf {
+        match self.project_type {
+            ProjectType::Fullstack => root.join("web"),
+            ProjectType::Lib | ProjectType::App => root.to_path_buf(),
+        }
+    }
+
+    fn plan_versions(&self, template_path: &Path) -> Result<(ResolvedPolicy, TemplateConfig)> {
+        let config = TemplateConfig::load(template_path)?;
+        let policy = self
+            .versions
+            .resolve(template_path, &config, self.package_manager)?;
+        Ok((policy, config))
+    }
+
+    /// A frozen install only works while package.json still declares what
+    /// the template's lockfile was made for; features and pins rewrite it.
+    fn lockfile_matches(&self, template_path: &Path, install_dir: &Path) -> bool {
+        let read = |dir: &Path| fs::read_to_string(dir.join("package.json"));
+        match (read(template_path), read(install_dir)) {
+            (Ok(before), Ok(after)) => !dependencies_changed(&before, &after),
+            _ => false,
+        }
+    }
+
+    fn write_manifest(&self, output: &mut OutputDir) -> Result<()> {
+        let root = output.root().to_path_buf();
+        if !output.prepare_write(&root.join(MANIFEST_FILE))? {
+            return Ok(());
+        }
+        let manifest = Manifest {
+            project: ProjectSection {
+                name: Some(self.project_name.clone()),
+                kind: Some(self.project_type.as_str().to_string()),
+                package_manager: Some(self.package_manager.name().to_string()),
+            },
+            ..Default::default()
+        };
+        manifest.write(output.fs(), &root)
+    }
+
+    /// Record the installed versions in nullslate.toml, if we wrote one.
+    fn record_versions(&self, root: &Path, versions: &BTreeMap<String, String>) -> Result<()> {
+        let Some(mut manifest) = Manifest::load(root)? else {
+            return Ok(());
+        };
+        manifest.packages = versions.clone();
+        manifest.write(&mut RealFs, root)
+    }
+
+    fn render(
+        &mut self,
+        template_path: &Path,
+        fs: &mut dyn FileSystem,
+        root: &Path,
+        mode: WriteMode,
+        policy: ResolvedPolicy,
+        config: &TemplateConfig,
+    ) -> Result<(CopyReport, Vec<String>)> {
+        self.emit(Event::StepStarted {
+            step: Step::ProcessFiles,
+        });
+        // File events go out as each file is written, so the callback moves
+        // into the output for the duration
+        let mut progress = self.on_progress.take();
+        let mut output = OutputDir::with_fs(root, mode, fs).on_event(|event| {
+            if let Some(callback) = progress.as_mut() {
+                callback(event);
+            }
+        });
+        let result = self.render_files(template_path, &mut output, policy, config);
+        let report = output.into_report();
+        self.on_progress = progress;
+        let warnings = result?;
+        self.emit(Event::StepFinished {
+            step: Step::ProcessFiles,
+        });
+        for message in &warnings {
+            self.emit(Event::Warning {
+                message: message.clone(),
+            });
+        }
+        Ok((report, warnings))
+    }
+
+    fn render_files(
+        &self,
+        template_path: &Path,
+        output: &mut OutputDir,
+        policy: ResolvedPolicy,
+        config: &TemplateConfig,
+    ) -> Result<Vec<String>> {
+        let vars = self.template_vars()?;
+        let pm = self.package_manager;
+        let warnings = match self.project_type {
+            ProjectType::App => render_app(template_path, output, &vars, &self.features, pm)?,
+            ProjectType::Fullstack => {
+                scaffold_fullstack(template_path, output, &vars, &self.features, pm)?
+            }
+            ProjectType::Lib => render_lib(template_path, output, &vars, &self.features, pm)?,
+        };
+        write_env_examples(output)?;
+        if policy == ResolvedPolicy::Pinned {
+            let package_json = self.js_dir(output.root()).join("package.json");
+            let relative = package_json.strip_prefix(output.root()).unwrap_or(&package_json);
+            if !output.report.was_skipped(relative) && output.fs().exists(&package_json) {
+                apply_pins(output.fs(), package_json.parent().unwrap(), &config.packages)?;
+                output.record_edit(&package_json);
+            }
+        }
+        self.write_manifest(output)?;
+        Ok(warnings)
+    }
+
+    pub fn scaffold(mut self) -> Result<ScaffoldReport> {
+        let name = self.name()?;
+
+        let output_path = self.resolved_output_path(&name);
+        check_output_dir(&output_path, self.write_mode)?;
+
+        // Checked before copying so a repo we are about to create doesn't count
+        let inside_repo = is_inside_git_work_tree(&output_path);
+
+        // Keeps the clone alive until rendering is done
+        let temp_dir = tempfile::tempdir()?;
+        let source = self.resolved_template();
+        let template_path = self.fetch_template(&source, temp_dir.path())?;
+        let (version_policy, config) = self.plan_versions(&template_path)?;
+        let (files, mut warnings) = self.render(
+            &template_path,
+            &mut RealFs,
+            &output_path,
+            self.write_mode,
+            version_policy,
+            &config,
+        )?;
+
+        // Install before committing so the lockfile and resolved versions
+        // land in the initial commit.
+        let mut versions = BTreeMap::new();
+        if self.install {
+            let install_dir = self.js_dir(&output_path);
+            let managed = config.managed_packages();
+            let frozen = version_policy == ResolvedPolicy::Lockfile
+                && self.lockfile_matches(&template_path, &install_dir);
+            if version_policy == ResolvedPolicy::Lockfile && !frozen {
+                let message = "package.json dependencies no longer match the template's lockfile, \
+                               so it will be updated by a regular install"
+                    .to_string();
+                self.emit(Event::Warning {
+                    message: message.clone(),
+                });
+                warnings.push(message);
+            }
+            self.emit(Event::StepStarted { step: Step::Install });
+            let install_warnings = install_deps(
+                &install_dir,
+                self.package_manager,
+                version_policy,
+                frozen,
+                &managed,
+            )?;
+            self.emit(Event::StepFinished { step: Step::Install });
+            for message in &install_warnings {
+                self.emit(Event::Warning {
+                    message: message.clone(),
+                });
+            }
+            warnings.extend(install_warnings);
+            self.emit(Event::InstallFinished {
+                path: install_dir.clone(),
+            });
+
+            versions = installed_versions(&install_dir, &managed);
+            if !versions.is_empty() && !files.was_skipped(MANIFEST_FILE) {
+                self.record_versions(&output_path, &versions)?;
+            }
+            self.emit(Event::VersionsResolved {
+                policy: version_policy.as_str().to_string(),
+                packages: versions.clone(),
+            });
+        }
+
+        let mut git_outcome = GitOutcome::default();
+        let mut git_error = None;
+        if self.git {
+            if inside_repo {
+                self.emit(Event::GitSkipped {
+                    reason: "inside existing work tree".to_string(),
+                });
+                let ignored = self.git_options.repo_settings();
+                if !ignored.is_empty() {
+                    let message = format!(
+                        "Ignoring the git {} setting{}: {} is inside an existing work tree",
+                        ignored.join(", "),
+                        if ignored.len() == 1 { "" } else { "s" },
+                        output_path.display()
+                    );
+                    self.emit(Event::Warning {
+                        message: message.clone(),
+                    });
+                    warnings.push(message);
+                }
+            } else {
+                self.emit(Event::StepStarted { step: Step::GitInit });
+                let (outcome, result) = init_repo(&output_path, &self.git_options);
+                git_outcome = outcome;
+                // Files are already written, so a git failure is reported
+                // rather than failing the whole scaffold.
+                match result {
+                    Ok(()) => self.emit(Event::StepFinished { step: Step::GitInit }),
+                    Err(e) => {
+                        let message = format!("{e:#}");
+                        self.emit(Event::StepFailed {
+                            step: Step::GitInit,
+                            message: message.clone(),
+                        });
+                        warnings.push(message.clone());
+                        git_error = Some(message);
+                    }
+                }
+                if git_outcome.committed {
+                    self.emit(Event::GitCommitted {
+                        path: output_path.clone(),
+                    });
+                }
+            }
+        }
+
+        let origin = dev_origin(&RealFs, &self.js_dir(&output_path));
+        self.emit(Event::Finished {
+            project: self.project_name.clone(),
+            path: output_path.clone(),
+        });
+
+        Ok(ScaffoldReport {
+            project_name: self.project_name,
+            project_type: self.project_type,
+            output_path,
+            template: source,
+            files,
+            git_initialized: git_outcome.initialized,
+            git_committed: git_outcome.committed,
+            git_error,
+            installed: self.install,
+            version_policy,
+            versions,
+            auth_callbacks: self
+                .features
+                .providers()
+                .iter()
+                .filter_map(|provider| provider.callback_url(&origin))
+                .collect(),
+            warnings,
+        })
+    }
+
+    /// Render the project into memory without touching the output directory.
+    ///
+    /// Git and install steps are skipped. In merge and force mode, files
+    /// already on disk are read through, so the report shows what would be
+    /// kept or backed up. Use [`RenderedProject::diff`] to compare it with
+    /// disk.
+    pub fn render_in_memory(mut self) -> Result<RenderedProject> {
+        let name = self.name()?;
+
+        let output_path = self.resolved_output_path(&name);
+        let temp_dir = tempfile::tempdir()?;
+        let source = self.resolved_template();
+        let template_path = self.fetch_template(&source, temp_dir.path())?;
+
+        let (version_policy, config) = self.plan_versions(&template_path)?;
+
+        let mut memory = match self.write_mode {
+            WriteMode::Create => MemoryFs::new(),
+            WriteMode::Merge | WriteMode::Force => MemoryFs::over_disk(),
+        };
+        let (files, _) = self.render(
+            &template_path,
+            &mut memory,
+            &output_path,
+            self.write_mode,
+            version_policy,
+            &config,
+        )?;
+
+        self.emit(Event::Finished {
+            project: self.project_name.clone(),
+            path: output_path.clone(),
+        });
+
+        Ok(RenderedProject {
+            fs: memory,
+            output_path,
+            files,
+        })
+    }
+}
+
+fn render_app(
+    template_path: &Path,
+    output: &mut OutputDir,
+    vars: &TemplateVars,
+    features: &Features,
+    pm: PackageManager,
+) -> Result<Vec<String>> {
+    let output_path = output.root().to_path_buf();
+    let lang = features.lang.as_str();
+    let providers = features.providers();
+    let extra_skips: Vec<String> = db_module_variants()
+        .into_iter()
+        .chain(provider_skips(&providers))
+        .collect();
+    let mut files_to_skip: Vec<&str> = get_files_to_skip(
+        features.docs,
+        features.auth,
+        features.db.is_enabled(),
+        features.orm,
+    );
+    files_to_skip.extend(extra_skips.iter().map(String::as_str));
+    files_to_skip.extend(pm.foreign_lockfiles());
+    let variants = select_variants(template_path, features.lang, &files_to_skip)?;
+    files_to_skip.extend(variants.skip_patterns());
+    copy_template(template_path, output, vars, &files_to_skip)?;
+
+    let is_js = features.lang == Language::JavaScript;
+    if is_js {
+        rewrite_references(output, &variants.renamed)?;
+    }
+
+    if !output.report.was_skipped("package.json") {
+        update_package_json(
+            output.fs(),
+            &output_path,
+            lang,
+            features.docs,
+            features.auth,
+            features.db,
+            features.orm,
+        )?;
+        update_auth_packages(output.fs(), &output_path, &providers)?;
+        if is_js {
+            convert_package_json(output.fs(), &output_path, &variants.renamed)?;
+        }
+        output.record_edit(&output_path.join("package.json"));
+    }
+
+    if !features.auth && !ROOT_LAYOUTS.iter().any(|layout| output.report.was_skipped(layout)) {
+        if let Some(layout) = cleanup_layout_for_no_auth(output.fs(), &output_path)? {
+            output.record_edit(&layout);
+        }
+    }
+
+    let mut warnings: Vec<String> = variants.warning().into_iter().collect();
+    if features.auth {
+        generate_env_file(&output_path, output, &providers)?;
+        warnings.extend(write_provider_files(output, &output_path, &providers, !is_js)?);
+    }
+
+    write_db_module(template_path, output, features.db, features.orm, !is_js)?;
+    write_orm_files(output, features.db, features.orm, !is_js)?;
+    let db_name = vars.get("crate_name").map(String::as_str).unwrap_or("app");
+    if let Some(url) = features.db.default_url(db_name) {
+        write_database_url(output, &output_path, &url)?;
+    }
+
+    Ok(warnings)
+}
+
+fn render_lib(
+    template_path: &Path,
+    output: &mut OutputDir,
+    vars: &TemplateVars,
+    features: &Features,
+    pm: PackageManager,
+) -> Result<Vec<String>> {
+    let lang = features.lang.as_str();
+    let mut files_to_skip: Vec<&str> =
+        get_lib_files_to_skip(lang, features.react, features.css, features.testing);
+    files_to_skip.extend(pm.foreign_lockfiles());
+    let variants = select_variants(template_path, features.lang, &files_to_skip)?;
+    files_to_skip.extend(variants.skip_patterns());
+    copy_template(template_path, output, vars, &files_to_skip)?;
+
+    let is_js = features.lang == Language::JavaScript;
+    if is_js {
+        rewrite_references(output, &variants.renamed)?;
+    }
+
+    if !output.report.was_skipped("package.json") {
+        let output_path = output.root().to_path_buf();
+        update_lib_package_json(output.fs(), &output_path, lang, features.react, features.css, features.testing)?;
+        if let Some(name) = vars.get("package_name") {
+            set_package_name(output.fs(), &output_path, name)?;
+        }
+        if is_js {
+            convert_package_json(output.fs(), &output_path, &variants.renamed)?;
+        }
+        output.record_edit(&output_path.join("package.json"));
+    }
+
+    Ok(variants.warning().into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use crate::vfs::FileSystem;
+    use std::fs;
+
+    fn local_template() -> tempfile::TempDir {
+        local_template_with(&[])
+    }
+
+    /// The basic template, with `files` added or replaced.
+    fn local_template_with(files: &[(&str, &str)]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let base = [
+            ("package.json", r#"{"name": "{{project_name}}", "dependencies": {"pg": "^8"}}"#),
+            ("README.md", "# {{project_name}} by {{author}}\n"),
+            ("template.json", "{}"),
+        ];
+        for (file, contents) in base.iter().chain(files) {
+            let path = dir.path().join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        dir
+    }
+
+    const JS_ROOT_LAYOUT: &str = "import { SessionProvider } from \"@/components/session-provider\"\n";
+
+    /// An app template with TypeScript and JavaScript variants side by side.
+    const JS_APP_FILES: &[(&str, &str)] = &[
+        ("package.json", r#"{"name": "{{project_name}}", "devDependencies": {"typescript": "^5"}}"#),
+        ("index.html", r#"<script src="/src/main.tsx"></script>"#),
+        ("src/main.tsx", ""),
+        ("src/main.jsx", ""),
+        ("src/lib/auth.ts", ""),
+        ("src/lib/auth.js", ""),
+        ("src/routes/__root.tsx", JS_ROOT_LAYOUT),
+        ("src/routes/__root.jsx", JS_ROOT_LAYOUT),
+    ];
+
+    #[test]
+    fn validate_project_name_rules() {
+        assert!(validate_project_name("my-app").is_ok());
+        assert!(validate_project_name("a").is_ok());
+        assert!(validate_project_name("My App").is_err());
+        assert!(validate_project_name("-app").is_err());
+    }
+
+    #[test]
+    fn scaffold_from_local_template() {
+        let template = local_template();
+        let out = tempfile::tempdir().unwrap();
+        let output_path = out.path().join("my-app");
+
+        let report = Scaffolder::new("my-app")
+            .template(TemplateSource::Local(template.path().to_path_buf()))
+            .variable("author", "acme")
+            .output_path(&output_path)
+            .git(false)
+            .install(false)
+            .scaffold()
+            .unwrap();
+
+        let readme = fs::read_to_string(output_path.join("README.md")).unwrap();
+        assert_eq!(readme, "# my-app by acme\n");
+        assert!(!output_path.join("template.json").exists());
+        assert!(report.files.edited.contains(&PathBuf::from("package.json")));
+        assert!(!report.git_initialized);
+
+        let manifest = Manifest::load(&output_path).unwrap().unwrap();
+        assert_eq!(manifest.project.package_manager.as_deref(), Some("bun"));
+        assert_eq!(manifest.project.kind.as_deref(), Some("app"));
+    }
+
+    #[test]
+    fn scaffold_reports_progress_in_order() {
+        let template = local_template();
+        let out = tempfile::tempdir().unwrap();
+        let events = RefCell::new(Vec::new());
+
+        Scaffolder::new("my-app")
+            .template(TemplateSource::Local(template.path().to_path_buf()))
+            .output_path(out.path().join("my-app"))
+            .git(false)
+            .install(false)
+            .on_progress(|e| events.borrow_mut().push(e.clone()))
+            .scaffold()
+            .unwrap();
+
+        let events = events.into_inner();
+        assert_eq!(
+            events.first(),
+            Some(&Event::StepStarted {
+                step: Step::FetchTemplate
+            })
+        );
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, Event::PackageJsonEdited { .. })));
+        assert!(matches!(events.last(), Some(Event::Finished { .. })));
+
+        // File events arrive while the files are processed, not afterwards
+        let position = |wanted: &Event| events.iter().position(|e| e == wanted).unwrap();
+        let started = position(&Event::StepStarted { step: Step::ProcessFiles });
+        let finished = position(&Event::StepFinished { step: Step::ProcessFiles });
+        let copied = events
+            .iter()
+            .position(|e| matches!(e, Event::FileCopied { .. }))
+            .unwrap();
+        assert!(started < copied && copied < finished);
+    }
+
+    #[test]
+    fn render_in_memory_leaves_disk_untouched() {
+        let template = local_template();
+        let out = tempfile::tempdir().unwrap();
+        let output_path = out.path().join("my-app");
+
+        let rendered = Scaffolder::new("my-app")
+            .template(TemplateSource::Local(template.path().to_path_buf()))
+            .variable("author", "acme")
+            .output_path(&output_path)
+            .render_in_memory()
+            .unwrap();
+
+        assert!(!output_path.exists());
+        let readme = rendered.fs.read_to_string(&output_path.join("README.md")).unwrap();
+        assert_eq!(readme, "# my-app by acme\n");
+        let package = rendered.fs.read_to_string(&output_path.join("package.json")).unwrap();
+        assert!(!package.contains("\"pg\""));
+        assert!(rendered
+            .diff()
+            .iter()
+            .all(|d| matches!(d, DiffEntry::Added(_))));
+
+        rendered.write_to_disk().unwrap();
+        assert_eq!(
+            fs::read_to_string(output_path.join("README.md")).unwrap(),
+            "# my-app by acme\n"
+        );
+    }
+
+    #[test]
+    fn render_in_memory_follows_write_mode() {
+        let template = local_template();
+        let out = tempfile::tempdir().unwrap();
+        fs::write(out.path().join("README.md"), "mine").unwrap();
+        let render = |mode| {
+            Scaffolder::new("my-app")
+                .template(TemplateSource::Local(template.path().to_path_buf()))
+                .output_path(out.path())
+                .write_mode(mode)
+                .render_in_memory()
+                .unwrap()
+        };
+
+        let merged = render(WriteMode::Merge);
+        assert_eq!(merged.files.skipped, [PathBuf::from("README.md")]);
+        assert!(!merged.fs.files().any(|p| p.ends_with("README.md")));
+
+        let forced = render(WriteMode::Force);
+        assert_eq!(forced.files.backed_up, [PathBuf::from("README.md")]);
+        let backup = out.path().join(forced.files.backup_dir.unwrap()).join("README.md");
+        assert_eq!(forced.fs.read_to_string(&backup).unwrap(), "mine");
+
+        assert_eq!(fs::read_to_string(out.path().join("README.md")).unwrap(), "mine");
+        assert!(!out.path().join("package.json").exists());
+    }
+
+    #[test]
+    fn render_in_memory_hash_ignores_output_path() {
+        let template = local_template();
+        let render = |path: &str| {
+            Scaffolder::new("my-app")
+                .template(TemplateSource::Local(template.path().to_path_buf()))
+                .variable("author", "acme")
+                .output_path(path)
+                .render_in_memory()
+                .unwrap()
+                .hash()
+        };
+        assert_eq!(render("one/my-app"), render("two/my-app"));
+    }
+
+    #[test]
+    fn render_skips_other_package_managers_lockfiles() {
+        let template = local_template();
+        fs::write(template.path().join("bun.lockb"), "lock").unwrap();
+
+        let rendered = Scaffolder::new("my-app")
+            .template(TemplateSource::Local(template.path().to_path_buf()))
+            .output_path("my-app")
+            .package_manager(PackageManager::Pnpm)
+            .render_in_memory()
+            .unwrap();
+
+        assert!(!rendered.fs.exists(Path::new("my-app/bun.lockb")));
+        let manifest = rendered.fs.read_to_string(Path::new("my-app/nullslate.toml")).unwrap();
+        assert!(manifest.contains("package_manager = \"pnpm\""));
+    }
+
+    #[test]
+    fn template_pins_are_written_to_package_json() {
+        let template = local_template();
+        fs::write(
+            template.path().join("package.json"),
+            r#"{"name": "{{project_name}}", "dependencies": {"@thesandybridge/ui": "^1.0.0"}}"#,
+        )
+        .unwrap();
+        fs::write(
+            template.path().join("template.json"),
+            r#"{"packages": {"@thesandybridge/ui": "1.4.2"}}"#,
+        )
+        .unwrap();
+
+        let rendered = Scaffolder::new("my-app")
+            .template(TemplateSource::Local(template.path().to_path_buf()))
+            .render_in_memory()
+            .unwrap();
+
+        let package: serde_json::Value = serde_json::from_str(
+            &rendered.fs.read_to_string(Path::new("my-app/package.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(package["dependencies"]["@thesandybridge/ui"], "1.4.2");
+    }
+
+    #[test]
+    fn lockfile_install_is_frozen_only_while_dependencies_match() {
+        let template = local_template();
+        fs::write(template.path().join("bun.lock"), "{}").unwrap();
+        let out = tempfile::tempdir().unwrap();
+        let scaffold = |name: &str| {
+            let report = Scaffolder::new(name)
+                .template(TemplateSource::Local(template.path().to_path_buf()))
+                .output_path(out.path().join(name))
+                .git(false)
+                .install(false)
+                .scaffold()
+                .unwrap();
+            assert_eq!(report.version_policy, ResolvedPolicy::Lockfile);
+            Scaffolder::new(name).lockfile_matches(template.path(), &report.output_path)
+        };
+
+        // Without a database feature the template's pg driver is dropped
+        assert!(!scaffold("with-pg"));
+        fs::write(
+            template.path().join("package.json"),
+            r#"{"name": "{{project_name}}", "dependencies": {"react": "^19"}}"#,
+        )
+        .unwrap();
+        assert!(scaffold("react-only"));
+    }
+
+    #[test]
+    fn explicit_lockfile_policy_needs_template_lockfile() {
+        let template = local_template();
+        let result = Scaffolder::new("my-app")
+            .template(TemplateSource::Local(template.path().to_path_buf()))
+            .versions(VersionPolicy::Lockfile)
+            .render_in_memory();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn scoped_lib_uses_bare_dir_and_scoped_package_name() {
+        let template = local_template();
+        fs::write(
+            template.path().join("README.md"),
+            "{{project_name}} {{package_name}} {{component_name}} {{crate_name}} {{dir_name}}\n",
+        )
+        .unwrap();
+
+        let rendered = Scaffolder::new("@acme/fancy-widgets")
+            .project_type(ProjectType::Lib)
+            .template(TemplateSource::Local(template.path().to_path_buf()))
+            .render_in_memory()
+            .unwrap();
+
+        assert_eq!(rendered.output_path, PathBuf::from("fancy-widgets"));
+        let package = rendered.fs.read_to_string(Path::new("fancy-widgets/package.json")).unwrap();
+        assert!(package.contains("\"@acme/fancy-widgets\""), "{package}");
+        let readme = rendered.fs.read_to_string(Path::new("fancy-widgets/README.md")).unwrap();
+        assert_eq!(
+            readme,
+            "fancy-widgets @acme/fancy-widgets FancyWidgets fancy_widgets fancy-widgets\n"
+        );
+    }
+
+    #[test]
+    fn scoped_name_rejected_for_apps() {
+        let result = Scaffolder::new("@acme/site").git(false).install(false).scaffold();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn javascript_lib_uses_js_variants() {
+        let template = local_template_with(&[
+            (
+                "package.json",
+                r#"{"name": "{{project_name}}", "types": "dist/index.d.ts", "scripts": {"build": "tsup src/index.ts --dts"}}"#,
+            ),
+            ("src/index.ts", "export const a: number = 1\n"),
+            ("src/index.js", "export const a = 1\n"),
+            ("src/util.ts", "export {}\n"),
+            ("tsconfig.json", "{}"),
+        ]);
+
+        let events = RefCell::new(Vec::new());
+        let rendered = Scaffolder::new("my-lib")
+            .project_type(ProjectType::Lib)
+            .features(Features {
+                lang: Language::JavaScript,
+                ..Default::default()
+            })
+            .template(TemplateSource::Local(template.path().to_path_buf()))
+            .on_progress(|e| events.borrow_mut().push(e.clone()))
+            .render_in_memory()
+            .unwrap();
+
+        assert!(rendered.fs.exists(Path::new("my-lib/src/index.js")));
+        assert!(!rendered.fs.exists(Path::new("my-lib/src/index.ts")));
+        assert!(!rendered.fs.exists(Path::new("my-lib/tsconfig.json")));
+        let package = rendered.fs.read_to_string(Path::new("my-lib/package.json")).unwrap();
+        assert!(package.contains("tsup src/index.js"), "{package}");
+        assert!(!package.contains("\"types\""), "{package}");
+        assert!(events.into_inner().iter().any(
+            |e| matches!(e, Event::Warning { message } if message.contains("src/util.ts"))
+        ));
+    }
+
+    #[test]
+    fn javascript_app_uses_js_variants() {
+        let template = local_template_with(JS_APP_FILES);
+        let rendered = Scaffolder::new("my-app")
+            .features(Features {
+                lang: Language::JavaScript,
+                ..Default::default()
+            })
+            .template(TemplateSource::Local(template.path().to_path_buf()))
+            .render_in_memory()
+            .unwrap();
+
+        let fs = &rendered.fs;
+        assert!(fs.exists(Path::new("my-app/src/main.jsx")));
+        assert!(!fs.exists(Path::new("my-app/src/main.tsx")));
+        assert!(!fs.exists(Path::new("my-app/src/lib/auth.js")));
+        assert!(!fs.exists(Path::new("my-app/src/routes/__root.tsx")));
+        let index = fs.read_to_string(Path::new("my-app/index.html")).unwrap();
+        assert!(index.contains("/src/main.jsx"), "{index}");
+        let layout = fs.read_to_string(Path::new("my-app/src/routes/__root.jsx")).unwrap();
+        assert!(!layout.contains("SessionProvider"), "{layout}");
+        let package = fs.read_to_string(Path::new("my-app/package.json")).unwrap();
+        assert!(!package.contains("typescript"), "{package}");
+    }
+
+    #[test]
+    fn javascript_fullstack_web_uses_js_variants() {
+        let template = local_template_with(JS_APP_FILES);
+        fs::create_dir(template.path().join("fullstack")).unwrap();
+        fs::write(template.path().join("fullstack/Cargo.toml"), "[workspace]\n").unwrap();
+
+        let rendered = Scaffolder::new("my-app")
+            .project_type(ProjectType::Fullstack)
+            .features(Features {
+                lang: Language::JavaScript,
+                ..Default::default()
+            })
+            .template(TemplateSource::Local(template.path().to_path_buf()))
+            .render_in_memory()
+            .unwrap();
+
+        let fs = &rendered.fs;
+        assert!(fs.exists(Path::new("my-app/web/src/main.jsx")));
+        assert!(!fs.exists(Path::new("my-app/web/src/main.tsx")));
+        assert!(fs.exists(Path::new("my-app/Cargo.toml")));
+        let index = fs.read_to_string(Path::new("my-app/web/index.html")).unwrap();
+        assert!(index.contains("/src/main.jsx"), "{index}");
+    }
+
+    #[test]
+    fn fullstack_database_lives_in_the_api() {
+        let template = local_template();
+        let overlay = template.path().join("fullstack/api/src");
+        fs::create_dir_all(&overlay).unwrap();
+        fs::write(
+            template.path().join("fullstack/api/Cargo.toml"),
+            "[package]\nname = \"api\"\n",
+        )
+        .unwrap();
+        fs::write(overlay.join("main.rs"), "fn main() {}\n").unwrap();
+
+        let rendered = Scaffolder::new("my-app")
+            .project_type(ProjectType::Fullstack)
+            .features(Features {
+                db: Database::Sqlite,
+                ..Default::default()
+            })
+            .template(TemplateSource::Local(template.path().to_path_buf()))
+            .render_in_memory()
+            .unwrap();
+
+        let fs = &rendered.fs;
+        let web_package = fs.read_to_string(Path::new("my-app/web/package.json")).unwrap();
+        assert!(!web_package.contains("\"pg\""), "{web_package}");
+        assert!(fs.exists(Path::new("my-app/api/src/db.rs")));
+        let env = fs.read_to_string(Path::new("my-app/.env")).unwrap();
+        assert!(env.contains("sqlite://my_app.db"), "{env}");
+    }
+
+    #[test]
+    fn app_database_picks_driver_module_and_env() {
+        let template = local_template();
+        let lib = template.path().join("src/lib");
+        fs::create_dir_all(&lib).unwrap();
+        fs::write(lib.join("db.ts"), "// postgres\n").unwrap();
+        fs::write(lib.join("db.sqlite.ts"), "// sqlite from template\n").unwrap();
+
+        let render = |db| {
+            Scaffolder::new("my-app")
+                .features(Features {
+                    db,
+                    ..Default::default()
+                })
+                .template(TemplateSource::Local(template.path().to_path_buf()))
+                .render_in_memory()
+                .unwrap()
+        };
+
+        let rendered = render(Database::Mysql);
+        let fs = &rendered.fs;
+        let package = fs.read_to_string(Path::new("my-app/package.json")).unwrap();
+        assert!(package.contains("\"mysql2\""), "{package}");
+        assert!(!package.contains("\"pg\""), "{package}");
+        let module = fs.read_to_string(Path::new("my-app/src/lib/db.ts")).unwrap();
+        assert!(module.contains("mysql2/promise"), "{module}");
+        assert!(!fs.exists(Path::new("my-app/src/lib/db.sqlite.ts")));
+        let env = fs.read_to_string(Path::new("my-app/.env")).unwrap();
+        assert!(env.contains("DATABASE_URL=mysql://"), "{env}");
+
+        let rendered = render(Database::Sqlite);
+        let module = rendered.fs.read_to_string(Path::new("my-app/src/lib/db.ts")).unwrap();
+        assert_eq!(module, "// sqlite from template\n");
+
+        let rendered = render(Database::Postgres);
+        let module = rendered.fs.read_to_string(Path::new("my-app/src/lib/db.ts")).unwrap();
+        assert_eq!(module, "// postgres\n");
+    }
+
+    #[test]
+    fn app_database_module_replaces_the_copied_template_file() {
+        let template = local_template_with(&[("src/lib/db.ts", "// postgres\n")]);
+        let out = tempfile::tempdir().unwrap();
+        fs::write(out.path().join("notes.txt"), "mine").unwrap();
+
+        for mode in [WriteMode::Merge, WriteMode::Force] {
+            let rendered = Scaffolder::new("my-app")
+                .features(Features {
+                    db: Database::Mysql,
+                    ..Default::default()
+                })
+                .template(TemplateSource::Local(template.path().to_path_buf()))
+                .output_path(out.path())
+                .write_mode(mode)
+                .render_in_memory()
+                .unwrap();
+
+            let module = rendered.fs.read_to_string(&out.path().join("src/lib/db.ts")).unwrap();
+            assert!(module.contains("mysql2/promise"), "{mode:?}: {module}");
+            let db = PathBuf::from("src/lib/db.ts");
+            assert_eq!(rendered.files.written.iter().filter(|p| **p == db).count(), 1);
+            assert!(rendered.files.skipped.is_empty(), "{mode:?}: {:?}", rendered.files.skipped);
+            assert!(rendered.files.backed_up.is_empty(), "{mode:?}: {:?}", rendered.files.backed_up);
+        }
+    }
+
+    #[test]
+    fn app_orm_selects_files_scripts_and_module() {
+        let template = local_template();
+        fs::create_dir_all(template.path().join("src/lib")).unwrap();
+        fs::write(template.path().join("src/lib/db.ts"), "// raw pg\n").unwrap();
+        fs::write(template.path().join("kysely.config.ts"), "// kysely\n").unwrap();
+
+        let rendered = Scaffolder::new("my-app")
+            .features(Features {
+                db: Database::Postgres,
+                orm: Orm::Drizzle,
+                ..Default::default()
+            })
+            .template(TemplateSource::Local(template.path().to_path_buf()))
+            .render_in_memory()
+            .unwrap();
+
+        let fs = &rendered.fs;
+        assert!(!fs.exists(Path::new("my-app/kysely.config.ts")));
+        let config = fs.read_to_string(Path::new("my-app/drizzle.config.ts")).unwrap();
+        assert!(config.contains("dialect: \"postgresql\""), "{config}");
+        assert!(fs.exists(Path::new("my-app/src/db/schema.ts")));
+        let module = fs.read_to_string(Path::new("my-app/src/lib/db.ts")).unwrap();
+        assert!(module.contains("drizzle-orm/node-postgres"), "{module}");
+        let package = fs.read_to_string(Path::new("my-app/package.json")).unwrap();
+        assert!(package.contains("\"db:generate\": \"drizzle-kit generate\""), "{package}");
+        assert!(package.contains("\"drizzle-kit\""), "{package}");
+    }
+
+    #[test]
+    fn auth_providers_get_configs_env_keys_and_callbacks() {
+        let template = local_template();
+        let providers = template.path().join("src/lib/auth-providers");
+        fs::create_dir_all(&providers).unwrap();
+        fs::write(providers.join("github.ts"), "// github\n").unwrap();
+        fs::write(
+            template.path().join("src/lib/auth.ts"),
+            "import { providers } from \"./auth-providers\"\n",
+        )
+        .unwrap();
+        fs::write(
+            template.path().join("vite.config.ts"),
+            "export default defineConfig({ server: { port: 4000 } })\n",
+        )
+        .unwrap();
+        let out = tempfile::tempdir().unwrap();
+        let output_path = out.path().join("my-app");
+
+        let report = Scaffolder::new("my-app")
+            .features(Features {
+                auth: true,
+                auth_providers: vec![AuthProvider::Google, AuthProvider::Email],
+                ..Default::default()
+            })
+            .template(TemplateSource::Local(template.path().to_path_buf()))
+            .output_path(&output_path)
+            .git(false)
+            .install(false)
+            .scaffold()
+            .unwrap();
+
+        let providers = output_path.join("src/lib/auth-providers");
+        assert!(!providers.join("github.ts").exists());
+        assert!(providers.join("google.ts").exists());
+        assert!(providers.join("email.ts").exists());
+        let env = fs::read_to_string(output_path.join(".env")).unwrap();
+        assert!(env.contains("AUTH_GOOGLE_ID=") && env.contains("AUTH_EMAIL_SERVER="), "{env}");
+        assert!(!env.contains("AUTH_GITHUB_ID"), "{env}");
+        let example = fs::read_to_string(output_path.join(".env.example")).unwrap();
+        assert!(example.contains("# Google OAuth\nAUTH_GOOGLE_ID=\n"), "{example}");
+        assert!(example.contains("AUTH_SECRET=\n"), "{example}");
+        let package = fs::read_to_string(output_path.join("package.json")).unwrap();
+        assert!(package.contains("\"nodemailer\""), "{package}");
+        assert_eq!(
+            report.auth_callbacks,
+            vec!["http://localhost:4000/api/auth/callback/google".to_string()]
+        );
+        assert_eq!(report.warnings.len(), 1, "{:?}", report.warnings);
+        assert!(report.warnings[0].contains("database adapter"), "{:?}", report.warnings);
+    }
+
+    #[test]
+    fn git_settings_inside_an_existing_repo_are_reported() {
+        let template = local_template();
+        let repo = tempfile::tempdir().unwrap();
+        let status = std::process::Command::new("git")
+            .arg("init")
+            .current_dir(repo.path())
+            .stdout(std::process::Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let report = Scaffolder::new("my-app")
+            .template(TemplateSource::Local(template.path().to_path_buf()))
+            .output_path(repo.path().join("my-app"))
+            .git_options(GitOptions {
+                branch: Some("trunk".to_string()),
+                remote: Some("git@example.com:acme/my-app.git".to_string()),
+                ..Default::default()
+            })
+            .install(false)
+            .scaffold()
+            .unwrap();
+
+        assert!(!report.git_initialized);
+        assert!(report.git_error.is_none());
+        assert!(
+            report.warnings.iter().any(|w| w.contains("git branch, remote settings")),
+            "{:?}",
+            report.warnings
+        );
+    }
+
+    #[test]
+    fn scaffold_rejects_invalid_name() {
+        let result = Scaffolder::new("Bad Name").git(false).install(false).scaffold();
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,343 @@
+use anyhow::{Context, Result};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use rand::RngExt;
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::env::{ENCRYPTED_FILE, ENV_FILE};
+use crate::features::generate_random_secret;
+use crate::manifest::Manifest;
+use crate::vfs::FileSystem;
+
+/// Hex key used instead of a keyfile, e.g. in CI.
+pub const KEY_ENV_VAR: &str = "NULLSLATE_ENV_KEY";
+/// Overrides where keyfiles are kept.
+pub const KEYS_DIR_ENV_VAR: &str = "NULLSLATE_KEYS_DIR";
+
+const KEY_PREFIX: &str = "NULLSLATE-ENV-KEY-";
+const HEADER: &str = "nullslate-env v1";
+
+/// Shared symmetric key for `.env.enc` files. There are no per-person
+/// recipients: everyone holding the key can decrypt and re-encrypt, so it is
+/// shared like a password and kept in a keyfile outside the repo.
+pub struct SharedKey([u8; 32]);
+
+impl SharedKey {
+    /// A fresh key from the same generator as `AUTH_SECRET`.
+    pub fn generate() -> SharedKey {
+        SharedKey::from_hex(&generate_random_secret()).expect("random secret is 32 bytes of hex")
+    }
+
+    pub fn from_hex(hex_key: &str) -> Result<SharedKey> {
+        let bytes = hex::decode(hex_key.trim()).context("Env key is not valid hex")?;
+        let key: [u8; 32] = bytes
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("Env key must be 32 bytes (64 hex characters)"))?;
+        Ok(SharedKey(key))
+    }
+
+    /// Short fingerprint recorded in `.env.enc`, so a wrong key is reported
+    /// as such instead of as corrupt data. Keyfiles are named after it.
+    pub fn id(&self) -> String {
+        hex::encode(&Sha256::digest(self.0)[..8])
+    }
+
+    fn to_keyfile(&self, project: &str) -> String {
+        format!(
+            "# nullslate env key for {project}\n# key id: {}\n{KEY_PREFIX}{}\n",
+            self.id(),
+            hex::encode(self.0)
+        )
+    }
+
+    fn from_keyfile(contents: &str) -> Result<SharedKey> {
+        let line = contents
+            .lines()
+            .find_map(|line| line.trim().strip_prefix(KEY_PREFIX))
+            .ok_or_else(|| anyhow::anyhow!("No {KEY_PREFIX} line in keyfile"))?;
+        SharedKey::from_hex(line)
+    }
+
+    /// The header lines, which are authenticated along with the contents.
+    fn header(key_id: &str) -> String {
+        format!("{HEADER}\nkey-id: {key_id}\n")
+    }
+
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<String> {
+        let header = SharedKey::header(&self.id());
+        let cipher = XChaCha20Poly1305::new((&self.0).into());
+        let nonce: [u8; 24] = rand::rng().random();
+        let payload = Payload {
+            msg: plaintext,
+            aad: header.as_bytes(),
+        };
+        let ciphertext = cipher
+            .encrypt(XNonce::from_slice(&nonce), payload)
+            .map_err(|_| anyhow::anyhow!("Encryption failed"))?;
+        Ok(format!("{header}{}{}\n", hex::encode(nonce), hex::encode(ciphertext)))
+    }
+
+    pub fn decrypt(&self, armored: &str) -> Result<Vec<u8>> {
+        let mut lines = armored.lines();
+        if lines.next() != Some(HEADER) {
+            anyhow::bail!("Not a nullslate encrypted env file");
+        }
+        let key_id = lines
+            .next()
+            .and_then(|line| line.strip_prefix("key-id: "))
+            .unwrap_or_default();
+        if key_id != self.id() {
+            anyhow::bail!("Encrypted with key {key_id}, but the available key is {}", self.id());
+        }
+        let data = hex::decode(lines.collect::<String>().trim()).context("Corrupt encrypted env file")?;
+        if data.len() < 24 {
+            anyhow::bail!("Corrupt encrypted env file");
+        }
+        let (nonce, ciphertext) = data.split_at(24);
+        let header = SharedKey::header(key_id);
+        let payload = Payload {
+            msg: ciphertext,
+            aad: header.as_bytes(),
+        };
+        let cipher = XChaCha20Poly1305::new((&self.0).into());
+        cipher
+            .decrypt(XNonce::from_slice(nonce), payload)
+            .map_err(|_| anyhow::anyhow!("Decryption failed: the file was modified or the key is wrong"))
+    }
+}
+
+/// Name recorded in the keyfile, to tell keys apart: the manifest name, else
+/// the root directory's name.
+fn project_name(root: &Path) -> Result<String> {
+    let name = Manifest::load(root)?
+        .and_then(|manifest| manifest.project.name)
+        .or_else(|| root.file_name().map(|n| n.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| "project".to_string());
+    Ok(name)
+}
+
+/// The key id the project's `.env.enc` files were encrypted with, if any
+/// exist. Keyfiles are looked up by it, so projects that share a name don't
+/// share a key.
+fn project_key_id(fs: &dyn FileSystem, root: &Path) -> Option<String> {
+    [root.to_path_buf(), root.join("web")].iter().find_map(|dir| {
+        let contents = fs.read_to_string(&dir.join(ENCRYPTED_FILE)).ok()?;
+        let key_id = contents.lines().nth(1)?.strip_prefix("key-id: ")?;
+        Some(key_id.trim().to_string())
+    })
+}
+
+fn keys_dir() -> Result<PathBuf> {
+    if let Some(dir) = std::env::var_os(KEYS_DIR_ENV_VAR) {
+        return Ok(PathBuf::from(dir));
+    }
+    let config = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+        .ok_or_else(|| anyhow::anyhow!("Can't locate a config directory; set {KEYS_DIR_ENV_VAR}"))?;
+    Ok(config.join("nullslate").join("keys"))
+}
+
+/// Where the keyfile for the key with `key_id` lives.
+pub fn key_path(key_id: &str) -> Result<PathBuf> {
+    Ok(keys_dir()?.join(format!("{key_id}.key")))
+}
+
+/// The key for the project at `root`, from `NULLSLATE_ENV_KEY` or the
+/// keyfile matching its `.env.enc`, if either exists.
+pub fn load_key(fs: &dyn FileSystem, root: &Path) -> Result<Option<SharedKey>> {
+    if let Ok(hex_key) = std::env::var(KEY_ENV_VAR) {
+        return SharedKey::from_hex(&hex_key).map(Some);
+    }
+    let Some(key_id) = project_key_id(fs, root) else {
+        return Ok(None);
+    };
+    let path = key_path(&key_id)?;
+    if !path.exists() {
+        return Ok(None);
+    }
+    let contents = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    SharedKey::from_keyfile(&contents)
+        .with_context(|| format!("Invalid keyfile {}", path.display()))
+        .map(Some)
+}
+
+/// Why no key is available for the project, for error messages.
+pub fn missing_key_hint(fs: &dyn FileSystem, root: &Path) -> Result<String> {
+    Ok(match project_key_id(fs, root) {
+        Some(key_id) => format!(
+            "{ENCRYPTED_FILE} needs key {key_id}. Put its keyfile at {} or set {KEY_ENV_VAR}",
+            key_path(&key_id)?.display()
+        ),
+        None => format!("No {ENCRYPTED_FILE} found; run `ns env encrypt` first"),
+    })
+}
+
+/// Load the project's key, creating a keyfile for a new one when the
+/// project has no `.env.enc` yet. Returns the keyfile path when one was
+/// created.
+pub fn load_or_create_key(fs: &dyn FileSystem, root: &Path) -> Result<(SharedKey, Option<PathBuf>)> {
+    if let Some(key) = load_key(fs, root)? {
+        return Ok((key, None));
+    }
+    if project_key_id(fs, root).is_some() {
+        anyhow::bail!("{}", missing_key_hint(fs, root)?);
+    }
+    let key = SharedKey::generate();
+    let path = key_path(&key.id())?;
+    std::fs::create_dir_all(path.parent().unwrap())?;
+    write_keyfile(&path, &key.to_keyfile(&project_name(root)?))?;
+    Ok((key, Some(path)))
+}
+
+/// Create a keyfile only its owner can read, never replacing one.
+fn write_keyfile(path: &Path, contents: &str) -> Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(path)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    file.write_all(contents.as_bytes())?;
+    Ok(())
+}
+
+/// Encrypt `dir/.env` into `dir/.env.enc`. Returns false when there's no
+/// `.env`.
+pub fn encrypt_dir(fs: &mut dyn FileSystem, dir: &Path, key: &SharedKey) -> Result<bool> {
+    let env = dir.join(ENV_FILE);
+    if !fs.exists(&env) {
+        return Ok(false);
+    }
+    let plaintext = fs.read(&env)?;
+    fs.write(&dir.join(ENCRYPTED_FILE), key.encrypt(&plaintext)?.as_bytes())?;
+    Ok(true)
+}
+
+/// Decrypt `dir/.env.enc` into `dir/.env`. A `.env` with different contents
+/// is only replaced with `force`. Returns false when there's no `.env.enc`.
+pub fn decrypt_dir(fs: &mut dyn FileSystem, dir: &Path, key: &SharedKey, force: bool) -> Result<bool> {
+    let encrypted = dir.join(ENCRYPTED_FILE);
+    if !fs.exists(&encrypted) {
+        return Ok(false);
+    }
+    let plaintext = key
+        .decrypt(&fs.read_to_string(&encrypted)?)
+        .with_context(|| format!("Failed to decrypt {}", encrypted.display()))?;
+    let env = dir.join(ENV_FILE);
+    if !force && fs.exists(&env) && fs.read(&env)? != plaintext {
+        anyhow::bail!(
+            "{} has local changes; pass --force to overwrite it",
+            env.display()
+        );
+    }
+    fs.write(&env, &plaintext)?;
+    Ok(true)
+}
+
+/// The variables in `dir/.env.enc`, when it exists and a key is available.
+pub fn decrypted_vars(fs: &dyn FileSystem, root: &Path, dir: &Path) -> Result<Vec<(String, String)>> {
+    let encrypted = dir.join(ENCRYPTED_FILE);
+    if !fs.exists(&encrypted) {
+        return Ok(Vec::new());
+    }
+    let Some(key) = load_key(fs, root)? else {
+        return Ok(Vec::new());
+    };
+    let plaintext = key
+        .decrypt(&fs.read_to_string(&encrypted)?)
+        .with_context(|| format!("Failed to decrypt {}", encrypted.display()))?;
+    Ok(crate::env::parse(&String::from_utf8_lossy(&plaintext)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vfs::MemoryFs;
+
+    #[test]
+    fn round_trip_and_wrong_key() {
+        let key = SharedKey::generate();
+        let armored = key.encrypt(b"AUTH_SECRET=abc\n").unwrap();
+        assert!(armored.starts_with("nullslate-env v1\nkey-id: "));
+        assert_eq!(key.decrypt(&armored).unwrap(), b"AUTH_SECRET=abc\n");
+
+        let err = SharedKey::generate().decrypt(&armored).unwrap_err().to_string();
+        assert!(err.contains("Encrypted with key"), "{err}");
+
+        let mut tampered = armored.trim_end().to_string();
+        let last = if tampered.ends_with('0') { "1" } else { "0" };
+        tampered.replace_range(tampered.len() - 1.., last);
+        let err = key.decrypt(&tampered).unwrap_err().to_string();
+        assert!(err.contains("Decryption failed"), "{err}");
+    }
+
+    #[test]
+    fn keyfile_round_trip() {
+        let key = SharedKey::generate();
+        let keyfile = key.to_keyfile("my-app");
+        assert!(keyfile.starts_with("# nullslate env key for my-app\n"));
+        assert_eq!(SharedKey::from_keyfile(&keyfile).unwrap().id(), key.id());
+        assert!(SharedKey::from_hex("abcd").is_err());
+    }
+
+    #[test]
+    fn header_is_authenticated() {
+        let key = SharedKey::generate();
+        let armored = key.encrypt(b"A=1\n").unwrap();
+        let nonce_and_data = armored.lines().nth(2).unwrap();
+        let cipher = XChaCha20Poly1305::new((&key.0).into());
+        let data = hex::decode(nonce_and_data).unwrap();
+        let (nonce, ciphertext) = data.split_at(24);
+        assert!(cipher.decrypt(XNonce::from_slice(nonce), ciphertext).is_err());
+    }
+
+    #[test]
+    fn keyfiles_are_private_and_never_replaced() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("abc.key");
+        write_keyfile(&path, "first\n").unwrap();
+        assert!(write_keyfile(&path, "second\n").is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "first\n");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn key_id_comes_from_the_encrypted_files() {
+        let mut mem = MemoryFs::new();
+        let root = Path::new("app");
+        mem.create_dir_all(&root.join("web")).unwrap();
+        assert_eq!(project_key_id(&mem, root), None);
+        let key = SharedKey::generate();
+        mem.write(&root.join("web").join(ENV_FILE), b"A=1\n").unwrap();
+        encrypt_dir(&mut mem, &root.join("web"), &key).unwrap();
+        assert_eq!(project_key_id(&mem, root), Some(key.id()));
+    }
+
+    #[test]
+    fn encrypt_then_decrypt_dir() {
+        let mut mem = MemoryFs::new();
+        let dir = Path::new("app");
+        mem.create_dir_all(dir).unwrap();
+        let key = SharedKey::generate();
+        mem.write(&dir.join(ENV_FILE), b"A=1\n").unwrap();
+        assert!(encrypt_dir(&mut mem, dir, &key).unwrap());
+
+        mem.write(&dir.join(ENV_FILE), b"A=2\n").unwrap();
+        assert!(decrypt_dir(&mut mem, dir, &key, false).is_err());
+        assert!(decrypt_dir(&mut mem, dir, &key, true).unwrap());
+        assert_eq!(mem.read_to_string(&dir.join(ENV_FILE)).unwrap(), "A=1\n");
+    }
+}
@@ -0,0 +1,884 @@
+use anyhow::{Context, Result};
+use std::fmt;
+use std::io::{BufRead, BufReader, IsTerminal, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Once;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::cache::{Lookup, TaskCache};
+use crate::devforge::{Probe, Readiness};
+use crate::events::{self, Event, ServiceStatus, Stream, TaskOutcome, TaskTiming};
+
+/// Set by Ctrl-C (or SIGTERM/SIGHUP) while processes are supervised.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static HANDLER: Once = Once::new();
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+const PROBE_INTERVAL: Duration = Duration::from_millis(250);
+const PROBE_TIMEOUT: Duration = Duration::from_millis(500);
+/// How long a process gets to exit after being asked to before it is killed.
+const GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+const COLORS: [&str; 5] = ["36", "35", "33", "32", "34"];
+
+/// A process that exited unsuccessfully, or was interrupted. `ns` exits
+/// with its code.
+#[derive(Debug)]
+pub struct ProcessFailed {
+    pub code: i32,
+    message: String,
+}
+
+impl ProcessFailed {
+    pub(crate) fn new(name: &str, status: ExitStatus) -> ProcessFailed {
+        ProcessFailed {
+            code: exit_code(status),
+            message: format!("{name} exited with {status}"),
+        }
+    }
+
+    fn interrupted() -> ProcessFailed {
+        ProcessFailed {
+            // The shell convention for SIGINT
+            code: 130,
+            message: "Interrupted".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ProcessFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ProcessFailed {}
+
+fn exit_code(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    status.code().unwrap_or(1)
+}
+
+/// One process run alongside others, its output prefixed with `name`.
+pub(crate) struct Proc {
+    pub name: String,
+    pub program: String,
+    pub args: Vec<String>,
+    pub cwd: PathBuf,
+    pub envs: Vec<(String, String)>,
+    /// Shown in the status table.
+    pub ports: Vec<u16>,
+    /// Checked before the next process starts.
+    pub ready: Option<Readiness>,
+}
+
+struct Running {
+    name: String,
+    program: String,
+    child: Child,
+    status: Option<ExitStatus>,
+    ports: Vec<u16>,
+    ready_after: Option<Duration>,
+    /// Holds the process and everything it starts, so stopping it stops them.
+    #[cfg(windows)]
+    job: Option<job::Job>,
+}
+
+impl Running {
+    fn status(&self) -> ServiceStatus {
+        ServiceStatus {
+            service: self.name.clone(),
+            ports: self.ports.clone(),
+            ready_ms: self.ready_after.map(|after| after.as_millis() as u64),
+            exited: self.status.is_some(),
+            code: self.status.and_then(|status| status.code()),
+        }
+    }
+}
+
+/// Processes started together, their output prefixed and colored per name.
+struct Group {
+    running: Vec<Running>,
+    readers: Vec<thread::JoinHandle<()>>,
+    width: usize,
+    color: bool,
+}
+
+impl Group {
+    /// A group whose prefixes are padded to `width`.
+    fn new(width: usize) -> Group {
+        HANDLER.call_once(|| {
+            // Without a handler Ctrl-C would only stop us: the children have
+            // process groups of their own
+            let _ = ctrlc::set_handler(|| INTERRUPTED.store(true, Ordering::SeqCst));
+        });
+        INTERRUPTED.store(false, Ordering::SeqCst);
+        Group {
+            running: Vec::new(),
+            readers: Vec::new(),
+            width,
+            color: std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+        }
+    }
+
+    /// Spawn `proc`, coloring its prefix by `slot`. Returns its index.
+    fn start(&mut self, proc: Proc, slot: usize) -> Result<usize> {
+        let width = self.width;
+        let prefix = if self.color {
+            format!("\x1b[{}m{:width$} |\x1b[0m ", COLORS[slot % COLORS.len()], proc.name)
+        } else {
+            format!("{:width$} | ", proc.name)
+        };
+        let mut child = spawn(&proc)?;
+        #[cfg(windows)]
+        let job = job::Job::for_child(&child);
+        self.readers.extend(
+            child
+                .stdout
+                .take()
+                .map(|out| forward(out, &proc.program, &prefix, Stream::Stdout)),
+        );
+        self.readers.extend(
+            child
+                .stderr
+                .take()
+                .map(|err| forward(err, &proc.program, &prefix, Stream::Stderr)),
+        );
+        self.running.push(Running {
+            name: proc.name,
+            program: proc.program,
+            child,
+            status: None,
+            ports: proc.ports,
+            ready_after: None,
+            #[cfg(windows)]
+            job,
+        });
+        Ok(self.running.len() - 1)
+    }
+
+    /// Stop whatever still runs and wait for the output to drain.
+    fn finish(&mut self) {
+        stop(&mut self.running);
+        for reader in self.readers.drain(..) {
+            let _ = reader.join();
+        }
+    }
+
+    /// The error for a process that exited, if it failed.
+    fn result(&self, i: usize) -> Result<()> {
+        match self.running[i].status {
+            Some(status) if !status.success() => {
+                Err(ProcessFailed::new(&self.running[i].name, status).into())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Why supervision ended.
+enum Outcome {
+    Exited(usize),
+    NotReady(usize, Readiness),
+    Interrupted,
+}
+
+/// Start `procs` in order, waiting for each one's readiness check before
+/// starting the next, then run them until one exits and stop the rest.
+/// Fails with [`ProcessFailed`] for the first process that exited
+/// unsuccessfully.
+pub(crate) fn run_all(procs: Vec<Proc>) -> Result<()> {
+    if procs.is_empty() {
+        return Ok(());
+    }
+    let mut group = Group::new(procs.iter().map(|p| p.name.len()).max().unwrap_or(0));
+
+    let outcome = 'start: {
+        for (slot, proc) in procs.into_iter().enumerate() {
+            let ready = proc.ready.clone();
+            let i = match group.start(proc, slot) {
+                Ok(i) => i,
+                Err(err) => {
+                    group.finish();
+                    return Err(err);
+                }
+            };
+
+            let Some(ready) = ready else { continue };
+            let started = Instant::now();
+            loop {
+                if INTERRUPTED.load(Ordering::SeqCst) {
+                    break 'start Outcome::Interrupted;
+                }
+                if let Some(exited) = poll(&mut group.running)? {
+                    break 'start Outcome::Exited(exited);
+                }
+                if probe(&ready.probe) {
+                    let elapsed = started.elapsed();
+                    group.running[i].ready_after = Some(elapsed);
+                    events::emit(Event::ServiceReady {
+                        service: group.running[i].name.clone(),
+                        elapsed_ms: elapsed.as_millis() as u64,
+                    });
+                    break;
+                }
+                if started.elapsed() >= ready.timeout {
+                    break 'start Outcome::NotReady(i, ready);
+                }
+                thread::sleep(PROBE_INTERVAL);
+            }
+        }
+
+        events::emit(Event::ServicesStarted {
+            services: group.running.iter().map(Running::status).collect(),
+        });
+        loop {
+            if INTERRUPTED.load(Ordering::SeqCst) {
+                break Outcome::Interrupted;
+            }
+            if let Some(i) = poll(&mut group.running)? {
+                break Outcome::Exited(i);
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    };
+
+    if let Outcome::Exited(i) = outcome {
+        let running = &group.running;
+        let others: Vec<String> = (0..running.len())
+            .filter(|j| *j != i && running[*j].status.is_none())
+            .map(|j| running[j].name.clone())
+            .collect();
+        if !others.is_empty() {
+            events::emit(Event::ServicesStopping {
+                exited: running[i].name.clone(),
+                stopping: others,
+            });
+        }
+    }
+    group.finish();
+
+    match outcome {
+        Outcome::Interrupted => Err(ProcessFailed::interrupted().into()),
+        Outcome::NotReady(i, ready) => anyhow::bail!(
+            "{} wasn't ready after {}s ({})",
+            group.running[i].name,
+            ready.timeout.as_secs(),
+            ready.probe
+        ),
+        Outcome::Exited(i) => group.result(i),
+    }
+}
+
+/// A task for [`run_tasks`]: its steps run one after another once every
+/// task it depends on has finished.
+pub(crate) struct TaskRun {
+    pub name: String,
+    pub steps: Vec<Proc>,
+    pub depends_on: Vec<String>,
+    /// Skips the steps when the cache has a run with the same inputs.
+    pub cache: Option<TaskCache>,
+}
+
+enum TaskState {
+    Waiting,
+    /// Running one of its steps as process `proc` of the group.
+    Running { proc: usize },
+    Done,
+}
+
+/// Run `tasks`, each as soon as its dependencies are done, so independent
+/// ones run in parallel, at most `jobs` at a time. Stops everything on the
+/// first failure and fails with its [`ProcessFailed`].
+pub(crate) fn run_tasks(tasks: Vec<TaskRun>, jobs: Option<usize>) -> Result<()> {
+    let mut names = Vec::new();
+    let mut steps = Vec::new();
+    let mut caches = Vec::new();
+    let mut deps: Vec<Vec<usize>> = Vec::new();
+    for task in &tasks {
+        let indices = task
+            .depends_on
+            .iter()
+            .map(|dep| {
+                tasks
+                    .iter()
+                    .position(|t| t.name == *dep)
+                    .ok_or_else(|| anyhow::anyhow!("{} depends on unknown task {dep:?}", task.name))
+            })
+            .collect::<Result<Vec<usize>>>()?;
+        deps.push(indices);
+    }
+    for task in tasks {
+        names.push(task.name);
+        steps.push(task.steps.into_iter());
+        caches.push(task.cache);
+    }
+    let mut states: Vec<TaskState> = names.iter().map(|_| TaskState::Waiting).collect();
+    // Keys of cache misses, stored once the task succeeds
+    let mut keys: Vec<Option<String>> = names.iter().map(|_| None).collect();
+    let mut hits = Vec::new();
+    // Aggregates of other tasks are left out of the timing summary
+    let timed: Vec<bool> = (0..names.len()).map(|i| !steps[i].as_slice().is_empty() || caches[i].is_some()).collect();
+    let mut started: Vec<Option<Instant>> = names.iter().map(|_| None).collect();
+    let mut took: Vec<Option<Duration>> = names.iter().map(|_| None).collect();
+    let mut failed = None;
+    let run_started = Instant::now();
+    let mut group = Group::new(names.iter().map(String::len).max().unwrap_or(0));
+
+    let outcome: Result<()> = 'run: loop {
+        if INTERRUPTED.load(Ordering::SeqCst) {
+            break Err(ProcessFailed::interrupted().into());
+        }
+        let mut progressed = false;
+        for i in 0..states.len() {
+            match states[i] {
+                TaskState::Waiting => {
+                    if !deps[i].iter().all(|d| matches!(states[*d], TaskState::Done)) {
+                        continue;
+                    }
+                    // Checked only now, as inputs may be outputs of
+                    // dependencies, and before taking a job slot; a miss
+                    // keeps its key so it isn't checked again while waiting
+                    if let (Some(cache), None) = (&caches[i], &keys[i]) {
+                        let checked = cache
+                            .key(&names[i], steps[i].as_slice())
+                            .and_then(|key| Ok((cache.lookup(&key)?, key)));
+                        match checked {
+                            Ok((Lookup::Miss, key)) => {
+                                events::emit(Event::CacheMiss { task: names[i].clone() });
+                                keys[i] = Some(key);
+                            }
+                            Ok((lookup, _)) => {
+                                let restored = lookup == Lookup::Restored;
+                                events::emit(Event::CacheHit { task: names[i].clone(), restored });
+                                hits.push(i);
+                                states[i] = TaskState::Done;
+                                progressed = true;
+                                continue;
+                            }
+                            Err(err) => break 'run Err(err),
+                        }
+                    }
+                    let running = states.iter().filter(|s| matches!(s, TaskState::Running { .. })).count();
+                    if !steps[i].as_slice().is_empty() && jobs.is_some_and(|jobs| running >= jobs) {
+                        continue;
+                    }
+                    started[i] = Some(Instant::now());
+                }
+                TaskState::Running { proc } => {
+                    let status = match group.running[proc].child.try_wait() {
+                        Ok(Some(status)) => status,
+                        Ok(None) => continue,
+                        Err(err) => break 'run Err(err.into()),
+                    };
+                    exited(&mut group.running[proc], status);
+                    if !status.success() {
+                        failed = Some(i);
+                        break 'run group.result(proc);
+                    }
+                }
+                TaskState::Done => continue,
+            }
+            // Start the task's next step, if it has one left
+            progressed = true;
+            states[i] = match steps[i].next() {
+                Some(step) => match group.start(step, i) {
+                    Ok(proc) => TaskState::Running { proc },
+                    Err(err) => break 'run Err(err),
+                },
+                None => {
+                    if let (Some(cache), Some(key)) = (&caches[i], keys[i].as_ref()) {
+                        if let Err(err) = cache.store(key) {
+                            break 'run Err(err.context(format!("Failed to cache the outputs of {}", names[i])));
+                        }
+                    }
+                    let elapsed = started[i].map(|at| at.elapsed()).unwrap_or_default();
+                    took[i] = Some(elapsed);
+                    if timed[i] {
+                        events::emit(Event::TaskFinished {
+                            task: names[i].clone(),
+                            elapsed_ms: elapsed.as_millis() as u64,
+                        });
+                    }
+                    TaskState::Done
+                }
+            };
+        }
+        if states.iter().all(|state| matches!(state, TaskState::Done)) {
+            break Ok(());
+        }
+        if !progressed && !states.iter().any(|state| matches!(state, TaskState::Running { .. })) {
+            let stuck: Vec<&str> = (0..states.len())
+                .filter(|i| matches!(states[*i], TaskState::Waiting))
+                .map(|i| names[i].as_str())
+                .collect();
+            break Err(anyhow::anyhow!("Tasks depend on each other in a cycle: {}", stuck.join(", ")));
+        }
+        thread::sleep(POLL_INTERVAL);
+    };
+
+    group.finish();
+    let tasks = (0..names.len())
+        .filter(|i| timed[*i])
+        .map(|i| {
+            let ms = |d: Duration| Some(d.as_millis() as u64);
+            let (outcome, elapsed_ms) = match (took[i], started[i]) {
+                _ if hits.contains(&i) => (TaskOutcome::Cached, None),
+                (Some(took), _) => (TaskOutcome::Finished, ms(took)),
+                (None, Some(at)) if failed == Some(i) => (TaskOutcome::Failed, ms(at.elapsed())),
+                (None, Some(at)) => (TaskOutcome::Cancelled, ms(at.elapsed())),
+                (None, None) => (TaskOutcome::NotStarted, None),
+            };
+            TaskTiming {
+                task: names[i].clone(),
+                outcome,
+                elapsed_ms,
+            }
+        })
+        .collect();
+    events::emit(Event::TasksSummary {
+        tasks,
+        total_ms: run_started.elapsed().as_millis() as u64,
+        cache_hits: hits.iter().map(|i| names[*i].clone()).collect(),
+        cache_misses: keys.iter().filter(|key| key.is_some()).count(),
+    });
+    outcome
+}
+
+/// Whether the probe passes right now.
+fn probe(probe: &Probe) -> bool {
+    match probe {
+        Probe::Tcp(port) => connect(*port).is_some(),
+        Probe::Http { port, path } => connect(*port).is_some_and(|stream| http_ok(stream, *port, path)),
+    }
+}
+
+fn connect(port: u16) -> Option<TcpStream> {
+    ("localhost", port)
+        .to_socket_addrs()
+        .ok()?
+        .find_map(|addr| TcpStream::connect_timeout(&addr, PROBE_TIMEOUT).ok())
+}
+
+fn http_ok(mut stream: TcpStream, port: u16, path: &str) -> bool {
+    let request = format!("GET {path} HTTP/1.1\r\nHost: localhost:{port}\r\nConnection: close\r\n\r\n");
+    if stream.set_read_timeout(Some(PROBE_TIMEOUT)).is_err() || stream.write_all(request.as_bytes()).is_err() {
+        return false;
+    }
+    let mut status_line = String::new();
+    if BufReader::new(stream).read_line(&mut status_line).is_err() {
+        return false;
+    }
+    status_line.split_whitespace().nth(1) == Some("200")
+}
+
+fn spawn(proc: &Proc) -> Result<Child> {
+    events::emit(Event::ProcessStarted {
+        program: proc.program.clone(),
+        args: proc.args.clone(),
+        cwd: proc.cwd.clone(),
+    });
+    let mut command = Command::new(&proc.program);
+    command
+        .args(&proc.args)
+        .current_dir(&proc.cwd)
+        .envs(proc.envs.iter().map(|(k, v)| (k, v)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    #[cfg(unix)]
+    {
+        // A group per process, so stopping one also stops what it started
+        // (the server behind `bun run dev`, say)
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    command
+        .spawn()
+        .with_context(|| format!("failed to run {} for {}", proc.program, proc.name))
+}
+
+/// Record processes that have exited. Returns the first one that failed,
+/// else the first one that exited.
+fn poll(running: &mut [Running]) -> Result<Option<usize>> {
+    let (mut first, mut failed) = (None, None);
+    for (i, proc) in running.iter_mut().enumerate() {
+        if proc.status.is_some() {
+            continue;
+        }
+        if let Some(status) = proc.child.try_wait()? {
+            exited(proc, status);
+            first = first.or(Some(i));
+            if !status.success() {
+                failed = failed.or(Some(i));
+            }
+        }
+    }
+    Ok(failed.or(first))
+}
+
+fn exited(proc: &mut Running, status: ExitStatus) {
+    proc.status = Some(status);
+    events::emit(Event::ProcessExited {
+        program: proc.program.clone(),
+        code: status.code(),
+    });
+}
+
+/// Ask every process still running to stop, killing any that outlive the
+/// grace period.
+fn stop(running: &mut [Running]) {
+    for proc in running.iter_mut().filter(|p| p.status.is_none()) {
+        terminate(proc);
+    }
+    let deadline = Instant::now() + GRACE_PERIOD;
+    while running.iter().any(|p| p.status.is_none()) {
+        for proc in running.iter_mut().filter(|p| p.status.is_none()) {
+            match proc.child.try_wait() {
+                Ok(Some(status)) => exited(proc, status),
+                Ok(None) if Instant::now() >= deadline => {
+                    kill(proc);
+                    if let Ok(status) = proc.child.wait() {
+                        exited(proc, status);
+                    }
+                }
+                Ok(None) => {}
+                Err(_) => proc.status = Some(ExitStatus::default()),
+            }
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+#[cfg(unix)]
+fn terminate(proc: &mut Running) {
+    // SIGINT rather than SIGTERM: dev servers treat it as Ctrl-C and shut
+    // down cleanly
+    unsafe {
+        libc::kill(-(proc.child.id() as libc::pid_t), libc::SIGINT);
+    }
+}
+
+#[cfg(unix)]
+fn kill(proc: &mut Running) {
+    unsafe {
+        libc::kill(-(proc.child.id() as libc::pid_t), libc::SIGKILL);
+    }
+}
+
+// Windows has no signal to ask the whole tree to stop, so both end it
+#[cfg(windows)]
+fn terminate(proc: &mut Running) {
+    kill(proc);
+}
+
+#[cfg(windows)]
+fn kill(proc: &mut Running) {
+    match &proc.job {
+        Some(job) => job.terminate(),
+        None => {
+            let _ = proc.child.kill();
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn terminate(proc: &mut Running) {
+    kill(proc);
+}
+
+#[cfg(not(any(unix, windows)))]
+fn kill(proc: &mut Running) {
+    let _ = proc.child.kill();
+}
+
+/// Job Objects stand in for process groups on Windows: everything the
+/// child starts joins its job, and terminating the job ends them all.
+#[cfg(windows)]
+mod job {
+    use std::os::windows::io::AsRawHandle;
+    use std::process::Child;
+    use windows_sys::Win32::Foundation::{CloseHandle, HANDLE};
+    use windows_sys::Win32::System::JobObjects::{
+        AssignProcessToJobObject, CreateJobObjectW, JobObjectExtendedLimitInformation,
+        SetInformationJobObject, TerminateJobObject, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+        JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+    };
+
+    pub(super) struct Job(HANDLE);
+
+    impl Job {
+        /// A job holding `child`, closed (and its processes killed) on drop.
+        /// None if the job can't be set up, e.g. when `ns` itself runs in a
+        /// job that forbids nesting.
+        pub(super) fn for_child(child: &Child) -> Option<Job> {
+            unsafe {
+                let handle = CreateJobObjectW(std::ptr::null(), std::ptr::null());
+                if handle.is_null() {
+                    return None;
+                }
+                let job = Job(handle);
+                let mut limits = JOBOBJECT_EXTENDED_LIMIT_INFORMATION::default();
+                limits.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+                let limited = SetInformationJobObject(
+                    job.0,
+                    JobObjectExtendedLimitInformation,
+                    &limits as *const _ as *const _,
+                    std::mem::size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+                );
+                let assigned = AssignProcessToJobObject(job.0, child.as_raw_handle() as HANDLE);
+                (limited != 0 && assigned != 0).then_some(job)
+            }
+        }
+
+        pub(super) fn terminate(&self) {
+            unsafe {
+                TerminateJobObject(self.0, 1);
+            }
+        }
+    }
+
+    impl Drop for Job {
+        fn drop(&mut self) {
+            unsafe {
+                CloseHandle(self.0);
+            }
+        }
+    }
+}
+
+/// Print every line of `pipe` with `prefix`, or emit it as a
+/// `process_output` event in JSON mode.
+fn forward(
+    pipe: impl Read + Send + 'static,
+    program: &str,
+    prefix: &str,
+    stream: Stream,
+) -> thread::JoinHandle<()> {
+    let program = program.to_string();
+    let prefix = prefix.to_string();
+    thread::spawn(move || {
+        for line in BufReader::new(pipe).lines().map_while(|l| l.ok()) {
+            if events::is_jsonl() {
+                events::emit(Event::ProcessOutput {
+                    program: program.clone(),
+                    stream,
+                    line,
+                });
+            } else if stream == Stream::Stdout {
+                println!("{prefix}{line}");
+            } else {
+                eprintln!("{prefix}{line}");
+            }
+        }
+    })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn sh(name: &str, script: &str) -> Proc {
+        Proc {
+            name: name.to_string(),
+            program: "sh".to_string(),
+            args: vec!["-c".to_string(), script.to_string()],
+            cwd: std::env::temp_dir(),
+            envs: Vec::new(),
+            ports: Vec::new(),
+            ready: None,
+        }
+    }
+
+    #[test]
+    fn failing_process_stops_the_rest_and_reports_its_code() {
+        let started = Instant::now();
+        let err = run_all(vec![sh("api", "sleep 30"), sh("web", "exit 3")]).unwrap_err();
+        let failed = err.downcast_ref::<ProcessFailed>().unwrap();
+        assert_eq!(failed.code, 3);
+        assert!(failed.to_string().starts_with("web exited"), "{failed}");
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn failures_win_over_clean_exits_in_the_same_poll() {
+        let mut group = Group::new(3);
+        group.start(sh("ok", "true"), 0).unwrap();
+        group.start(sh("bad", "exit 5"), 1).unwrap();
+        thread::sleep(Duration::from_millis(500));
+        assert_eq!(poll(&mut group.running).unwrap(), Some(1));
+        group.finish();
+    }
+
+    #[test]
+    fn tcp_probe_needs_a_listener() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert!(probe(&Probe::Tcp(port)));
+        drop(listener);
+        assert!(!probe(&Probe::Tcp(port)));
+    }
+
+    #[test]
+    fn http_probe_needs_a_200() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            for status in ["503 Service Unavailable", "200 OK"] {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = String::new();
+                BufReader::new(&stream).read_line(&mut request).unwrap();
+                assert_eq!(request, "GET /health HTTP/1.1\r\n");
+                write!(stream, "HTTP/1.1 {status}\r\n\r\n").unwrap();
+            }
+        });
+        let health = Probe::Http { port, path: "/health".to_string() };
+        assert!(!probe(&health));
+        assert!(probe(&health));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn waits_for_readiness_before_starting_dependents() {
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("db-ready");
+        // Bound for the whole test, so no other process can take the port;
+        // the "database" answers 503 until it has written the marker
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let db_marker = marker.clone();
+        let db = thread::spawn(move || {
+            let up_at = Instant::now() + Duration::from_millis(500);
+            loop {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = String::new();
+                let _ = BufReader::new(&stream).read_line(&mut request);
+                let up = Instant::now() >= up_at;
+                if up {
+                    std::fs::write(&db_marker, "").unwrap();
+                }
+                let status = if up { "200 OK" } else { "503 Service Unavailable" };
+                let _ = write!(stream, "HTTP/1.1 {status}\r\n\r\n");
+                if up {
+                    break;
+                }
+            }
+        });
+
+        let mut waiter = sh("db", "sleep 30");
+        waiter.ready = Some(Readiness {
+            probe: Probe::Http { port, path: "/".to_string() },
+            timeout: Duration::from_secs(10),
+        });
+        let app = sh("app", &format!("test -f {}", marker.display()));
+        run_all(vec![waiter, app]).unwrap();
+        db.join().unwrap();
+    }
+
+    #[test]
+    fn readiness_timeout_stops_everything() {
+        let mut slow = sh("slow", "sleep 30");
+        slow.ready = Some(Readiness { probe: Probe::Tcp(1), timeout: Duration::from_millis(300) });
+        let err = run_all(vec![slow]).unwrap_err().to_string();
+        assert!(err.starts_with("slow wasn't ready"), "{err}");
+    }
+
+    fn task(name: &str, steps: &[&str], depends_on: &[&str]) -> TaskRun {
+        TaskRun {
+            name: name.to_string(),
+            steps: steps.iter().map(|script| sh(name, script)).collect(),
+            depends_on: depends_on.iter().map(|d| d.to_string()).collect(),
+            cache: None,
+        }
+    }
+
+    #[test]
+    fn tasks_run_independent_ones_in_parallel_after_dependencies() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("log");
+        let log = log.display();
+        let started = Instant::now();
+        run_tasks(vec![
+            task("a", &[&format!("sleep 1; echo a >> {log}")], &[]),
+            task("b", &[&format!("sleep 1; echo b1 >> {log}"), &format!("echo b2 >> {log}")], &[]),
+            task("c", &[&format!("echo c >> {log}")], &["a", "b"]),
+        ], None)
+        .unwrap();
+        // One after the other would take two seconds
+        assert!(started.elapsed() < Duration::from_millis(1800), "{:?}", started.elapsed());
+
+        let lines = std::fs::read_to_string(dir.path().join("log")).unwrap();
+        let lines: Vec<&str> = lines.lines().collect();
+        assert_eq!(lines.last(), Some(&"c"));
+        let b1 = lines.iter().position(|l| *l == "b1").unwrap();
+        assert!(lines[b1 + 1..].contains(&"b2"));
+    }
+
+    #[test]
+    fn jobs_limit_how_many_tasks_run_at_once() {
+        let started = Instant::now();
+        run_tasks(vec![task("a", &["sleep 0.5"], &[]), task("b", &["sleep 0.5"], &[])], Some(1)).unwrap();
+        assert!(started.elapsed() >= Duration::from_secs(1), "{:?}", started.elapsed());
+    }
+
+    #[test]
+    fn tasks_stop_on_first_failure() {
+        let started = Instant::now();
+        let err = run_tasks(vec![
+            task("slow", &["sleep 30"], &[]),
+            task("bad", &["exit 4", "echo unreachable"], &[]),
+            task("after", &["echo unreachable"], &["slow"]),
+        ], None)
+        .unwrap_err();
+        assert_eq!(err.downcast_ref::<ProcessFailed>().unwrap().code, 4);
+        assert!(started.elapsed() < Duration::from_secs(10));
+
+        let err = run_tasks(vec![task("a", &["true"], &["missing"])], None).unwrap_err();
+        assert!(err.to_string().contains("unknown task"), "{err}");
+    }
+
+    #[test]
+    fn cached_tasks_skip_unchanged_runs() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::write(root.join("input"), "1").unwrap();
+        let cached = |script: &str| {
+            let mut run = task("gen", &[script], &[]);
+            run.steps[0].cwd = root.to_path_buf();
+            run.cache = Some(TaskCache {
+                root: root.to_path_buf(),
+                base: root.to_path_buf(),
+                inputs: vec!["input".to_string()],
+                outputs: vec!["out".to_string()],
+            });
+            run
+        };
+        let script = "cat input > out; echo run >> runs";
+        run_tasks(vec![cached(script)], None).unwrap();
+        std::fs::remove_file(root.join("out")).unwrap();
+        run_tasks(vec![cached(script)], None).unwrap();
+        assert_eq!(std::fs::read_to_string(root.join("out")).unwrap(), "1");
+        assert_eq!(std::fs::read_to_string(root.join("runs")).unwrap(), "run\n");
+
+        std::fs::write(root.join("input"), "2").unwrap();
+        run_tasks(vec![cached(script)], None).unwrap();
+        assert_eq!(std::fs::read_to_string(root.join("out")).unwrap(), "2");
+    }
+
+    #[test]
+    fn clean_exit_stops_the_rest() {
+        let started = Instant::now();
+        run_all(vec![sh("api", "sleep 30"), sh("web", "true")]).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+}
@@ -0,0 +1,123 @@
+use anyhow::Result;
+use std::collections::BTreeMap;
+
+use crate::manifest::Task;
+
+/// Tasks `ns` runs itself unless `nullslate.toml` defines them.
+pub const BUILTIN_TASKS: [&str; 2] = ["dev", "build"];
+
+/// What a planned task runs.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Action<'a> {
+    Commands(&'a Task),
+    /// The built-in build for the project type.
+    Build,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct Planned<'a> {
+    pub name: String,
+    pub action: Action<'a>,
+    pub depends_on: Vec<String>,
+}
+
+/// `target` and every task it depends on, dependencies first. `build`
+/// falls back to the built-in when the manifest doesn't define it.
+pub(crate) fn plan<'a>(tasks: &'a BTreeMap<String, Task>, target: &str) -> Result<Vec<Planned<'a>>> {
+    let mut planned = Vec::new();
+    visit(tasks, target, &mut Vec::new(), &mut planned)?;
+    Ok(planned)
+}
+
+fn visit<'a>(
+    tasks: &'a BTreeMap<String, Task>,
+    name: &str,
+    stack: &mut Vec<String>,
+    planned: &mut Vec<Planned<'a>>,
+) -> Result<()> {
+    if planned.iter().any(|p| p.name == name) {
+        return Ok(());
+    }
+    if stack.iter().any(|n| n == name) {
+        stack.push(name.to_string());
+        anyhow::bail!("Tasks depend on each other in a cycle: {}", stack.join(" -> "));
+    }
+
+    let (action, depends_on) = match tasks.get(name) {
+        Some(task) => (Action::Commands(task), task.depends_on.clone()),
+        None if name == "build" => (Action::Build, Vec::new()),
+        None if name == "dev" => anyhow::bail!(
+            "{} depends on dev, which keeps running; only tasks defined in nullslate.toml can be dependencies",
+            stack.last().map(String::as_str).unwrap_or("A task")
+        ),
+        None => {
+            let defined: Vec<&str> = tasks.keys().map(String::as_str).collect();
+            let hint = if defined.is_empty() {
+                "nullslate.toml defines no tasks".to_string()
+            } else {
+                format!("nullslate.toml defines {}", defined.join(", "))
+            };
+            match stack.last() {
+                Some(parent) => anyhow::bail!("{parent} depends on unknown task {name:?}; {hint}"),
+                None => anyhow::bail!("Unknown task {name:?}; {hint}"),
+            }
+        }
+    };
+
+    stack.push(name.to_string());
+    for dep in &depends_on {
+        visit(tasks, dep, stack, planned)?;
+    }
+    stack.pop();
+
+    planned.push(Planned {
+        name: name.to_string(),
+        action,
+        depends_on,
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(commands: &[&str], depends_on: &[&str]) -> Task {
+        Task {
+            commands: commands.iter().map(|c| c.to_string()).collect(),
+            depends_on: depends_on.iter().map(|d| d.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn plans_dependencies_first_and_once() {
+        let tasks = BTreeMap::from([
+            ("ci".to_string(), task(&[], &["lint", "test"])),
+            ("lint".to_string(), task(&["cargo clippy"], &[])),
+            ("test".to_string(), task(&["cargo test"], &["build"])),
+            ("deploy".to_string(), task(&["./deploy.sh"], &["ci", "build"])),
+        ]);
+        let plan = plan(&tasks, "deploy").unwrap();
+        let names: Vec<&str> = plan.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["lint", "build", "test", "ci", "deploy"]);
+        assert_eq!(plan[1].action, Action::Build);
+    }
+
+    #[test]
+    fn rejects_cycles_and_unknown_tasks() {
+        let tasks = BTreeMap::from([
+            ("a".to_string(), task(&["a"], &["b"])),
+            ("b".to_string(), task(&["b"], &["a"])),
+            ("c".to_string(), task(&["c"], &["nope"])),
+            ("d".to_string(), task(&["d"], &["dev"])),
+        ]);
+        let err = plan(&tasks, "a").unwrap_err().to_string();
+        assert!(err.contains("a -> b -> a"), "{err}");
+        let err = plan(&tasks, "c").unwrap_err().to_string();
+        assert!(err.starts_with("c depends on unknown task \"nope\""), "{err}");
+        assert!(plan(&tasks, "d").unwrap_err().to_string().contains("depends on dev"));
+        let err = plan(&tasks, "lint").unwrap_err().to_string();
+        assert!(err.contains("nullslate.toml defines a, b, c, d"), "{err}");
+    }
+}
@@ -1,13 +1,260 @@
 use anyhow::{Context, Result};
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use walkdir::WalkDir;
 
+use crate::events::{Event, ProgressFn};
 use crate::features::should_skip_file;
+use crate::pm::PackageManager;
+use crate::scaffold::ProjectType;
+use crate::versions::ResolvedPolicy;
+use crate::vfs::{FileSystem, RealFs};
 
 const TEMPLATE_SUBDIR: &str = "template";
 
+const DEFAULT_TEMPLATE_REPO: &str = "https://github.com/nullslate/app-template.git";
+const DEFAULT_LIB_TEMPLATE_REPO: &str = "https://github.com/nullslate/lib-template.git";
+const DEFAULT_FULLSTACK_TEMPLATE_REPO: &str = "https://github.com/nullslate/app-template.git";
+
+/// Placeholder values substituted into text files as `{{name}}`.
+pub type TemplateVars = BTreeMap<String, String>;
+
+pub fn default_template_url(project_type: ProjectType) -> &'static str {
+    match project_type {
+        ProjectType::Lib => DEFAULT_LIB_TEMPLATE_REPO,
+        ProjectType::Fullstack => DEFAULT_FULLSTACK_TEMPLATE_REPO,
+        ProjectType::App => DEFAULT_TEMPLATE_REPO,
+    }
+}
+const BACKUP_DIR: &str = ".nullslate-backup";
+
+/// How to treat files that already exist in the output directory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WriteMode {
+    /// Output directory is new (or empty); nothing to collide with.
+    Create,
+    /// Keep existing files, only add missing ones.
+    Merge,
+    /// Overwrite existing files, moving the originals into a timestamped
+    /// directory under `.nullslate-backup/`.
+    Force,
+}
+
+/// Paths (relative to the output root) touched while scaffolding.
+#[derive(Debug, Default)]
+pub struct CopyReport {
+    pub written: Vec<PathBuf>,
+    pub skipped: Vec<PathBuf>,
+    pub backed_up: Vec<PathBuf>,
+    /// Where this run's backups went, e.g. `.nullslate-backup/20260101-120000`.
+    pub backup_dir: Option<PathBuf>,
+    /// Files rewritten in place after copying (package.json edits, layout cleanup).
+    pub edited: Vec<PathBuf>,
+}
+
+impl CopyReport {
+    pub fn was_skipped(&self, relative: impl AsRef<Path>) -> bool {
+        self.skipped.iter().any(|p| p == relative.as_ref())
+    }
+}
+
+/// The directory being scaffolded into, along with the collision policy and
+/// a record of what happened to each file.
+///
+/// All writes go through a `FileSystem`, so the same pipeline can render to
+/// disk or into a `MemoryFs`.
+pub struct OutputDir<'a> {
+    root: PathBuf,
+    mode: WriteMode,
+    fs: Box<dyn FileSystem + 'a>,
+    pub report: CopyReport,
+    on_event: Option<ProgressFn<'a>>,
+}
+
+impl<'a> OutputDir<'a> {
+    pub fn new(root: &Path, mode: WriteMode) -> Self {
+        Self::with_fs(root, mode, RealFs)
+    }
+
+    pub fn with_fs(root: &Path, mode: WriteMode, fs: impl FileSystem + 'a) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            mode,
+            fs: Box::new(fs),
+            report: CopyReport::default(),
+            on_event: None,
+        }
+    }
+
+    /// Called as each file is written, skipped, backed up or edited.
+    pub fn on_event(mut self, callback: impl FnMut(&Event) + 'a) -> Self {
+        self.on_event = Some(Box::new(callback));
+        self
+    }
+
+    fn emit(&mut self, event: Event) {
+        if let Some(callback) = self.on_event.as_mut() {
+            callback(&event);
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn into_report(self) -> CopyReport {
+        self.report
+    }
+
+    pub fn fs(&mut self) -> &mut dyn FileSystem {
+        self.fs.as_mut()
+    }
+
+    pub fn record_edit(&mut self, path: &Path) {
+        let relative = path.strip_prefix(&self.root).unwrap_or(path).to_path_buf();
+        if !self.report.edited.contains(&relative) {
+            let path = self.root.join(&relative);
+            self.report.edited.push(relative);
+            if path.file_name().is_some_and(|n| n == "package.json") {
+                self.emit(Event::PackageJsonEdited { path });
+            } else {
+                self.emit(Event::FileEdited { path });
+            }
+        }
+    }
+
+    /// Decide whether `dest` may be written, backing up the existing file in
+    /// force mode. Returns `false` when the file must be left alone.
+    ///
+    /// A file this run already wrote (a template file a feature replaces) is
+    /// its own to overwrite, and one it already kept stays kept.
+    pub fn prepare_write(&mut self, dest: &Path) -> Result<bool> {
+        let relative = dest.strip_prefix(&self.root).unwrap_or(dest).to_path_buf();
+        if self.report.written.contains(&relative) {
+            return Ok(true);
+        }
+        if self.report.skipped.contains(&relative) {
+            return Ok(false);
+        }
+
+        let write = match self.mode {
+            _ if !self.fs.exists(dest) => true,
+            WriteMode::Create => true,
+            WriteMode::Merge => false,
+            WriteMode::Force => {
+                self.back_up(dest, &relative)?;
+                true
+            }
+        };
+        if write {
+            self.report.written.push(relative);
+            self.emit(Event::FileCopied { path: dest.to_path_buf() });
+        } else {
+            self.report.skipped.push(relative);
+            self.emit(Event::FileSkipped { path: dest.to_path_buf() });
+        }
+        Ok(write)
+    }
+
+    /// Make way for a template directory at `dest`. A file in the way is
+    /// backed up in force mode and an error otherwise.
+    pub fn prepare_dir(&mut self, dest: &Path) -> Result<()> {
+        if !self.fs.exists(dest) || self.fs.is_dir(dest) {
+            return Ok(());
+        }
+        let relative = dest.strip_prefix(&self.root).unwrap_or(dest).to_path_buf();
+        if self.mode != WriteMode::Force {
+            anyhow::bail!(
+                "{} is a file, but the template has a directory there (use --force to replace it)",
+                relative.display()
+            );
+        }
+        self.back_up(dest, &relative)
+    }
+
+    /// Move `dest` into this run's backup directory. Each run gets its own,
+    /// so an earlier run's backups are never overwritten.
+    fn back_up(&mut self, dest: &Path, relative: &Path) -> Result<()> {
+        let backup_dir = match &self.report.backup_dir {
+            Some(dir) => dir.clone(),
+            None => {
+                let stamp = backup_stamp();
+                let mut dir = Path::new(BACKUP_DIR).join(&stamp);
+                let mut n = 1;
+                while self.fs.exists(&self.root.join(&dir)) {
+                    n += 1;
+                    dir = Path::new(BACKUP_DIR).join(format!("{stamp}-{n}"));
+                }
+                self.report.backup_dir = Some(dir.clone());
+                dir
+            }
+        };
+        let backup = self.root.join(backup_dir).join(relative);
+        if let Some(parent) = backup.parent() {
+            self.fs.create_dir_all(parent)?;
+        }
+        // Keep backups out of the initial commit
+        let ignore = self.root.join(BACKUP_DIR).join(".gitignore");
+        if !self.fs.exists(&ignore) {
+            self.fs.write(&ignore, b"*\n")?;
+        }
+        self.fs
+            .rename(dest, &backup)
+            .with_context(|| format!("Failed to back up {}", dest.display()))?;
+        self.report.backed_up.push(relative.to_path_buf());
+        self.emit(Event::FileBackedUp { path: dest.to_path_buf() });
+        Ok(())
+    }
+}
+
+/// The current UTC time as `YYYYMMDD-HHMMSS`.
+fn backup_stamp() -> String {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let (days, time) = (secs / 86_400, secs % 86_400);
+    // Civil date from days since the epoch (Howard Hinnant's algorithm)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}{month:02}{day:02}-{:02}{:02}{:02}",
+        time / 3_600,
+        time % 3_600 / 60,
+        time % 60
+    )
+}
+
+/// Check that `path` can be scaffolded into with the given mode.
+///
+/// Missing and empty directories are always fine; anything else needs
+/// `--merge` or `--force`.
+pub fn check_output_dir(path: &Path, mode: WriteMode) -> Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    if !path.is_dir() {
+        anyhow::bail!("'{}' exists and is not a directory", path.display());
+    }
+    let is_empty = fs::read_dir(path)?.next().is_none();
+    if !is_empty && mode == WriteMode::Create {
+        anyhow::bail!(
+            "Directory '{}' already exists and is not empty (use --merge or --force)",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
 pub fn clone_template(template_url: &str, dest: &Path) -> Result<()> {
     let clone_dir = dest.join("_clone");
 
@@ -29,11 +276,7 @@ pub fn clone_template(template_url: &str, dest: &Path) -> Result<()> {
     }
 
     // If the clone contains a template/ subdirectory, use that as the source
-    let source = if clone_dir.join(TEMPLATE_SUBDIR).is_dir() {
-        clone_dir.join(TEMPLATE_SUBDIR)
-    } else {
-        clone_dir.clone()
-    };
+    let source = template_root(&clone_dir);
 
     // Move contents from source to dest, skipping .git
     for entry in fs::read_dir(&source)? {
@@ -52,17 +295,51 @@ pub fn clone_template(template_url: &str, dest: &Path) -> Result<()> {
     Ok(())
 }
 
-pub fn process_template(content: &str, project_name: &str) -> String {
-    content.replace("{{project_name}}", project_name)
+/// Resolve the directory holding the template files inside a checkout: the
+/// `template/` subdirectory if there is one, otherwise the checkout itself.
+pub fn template_root(checkout: &Path) -> PathBuf {
+    if checkout.join(TEMPLATE_SUBDIR).is_dir() {
+        checkout.join(TEMPLATE_SUBDIR)
+    } else {
+        checkout.to_path_buf()
+    }
+}
+
+/// Substitute `{{key}}` placeholders in a single pass, so values are never
+/// scanned for placeholders themselves. Unknown keys are left as they are.
+pub fn process_template(content: &str, vars: &TemplateVars) -> String {
+    let mut result = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some(start) = rest.find("{{") {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let known = after
+            .find("}}")
+            .and_then(|end| vars.get(&after[..end]).map(|value| (end, value)));
+        match known {
+            Some((end, value)) => {
+                result.push_str(value);
+                rest = &after[end + 2..];
+            }
+            // Step over one brace only, so `{{{key}}}` still matches
+            None => {
+                result.push('{');
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
 }
 
 pub fn copy_template(
     temp_path: &Path,
-    output_path: &Path,
-    project_name: &str,
+    output: &mut OutputDir,
+    vars: &TemplateVars,
     files_to_skip: &[&str],
 ) -> Result<()> {
-    fs::create_dir_all(output_path)?;
+    let output_path = output.root().to_path_buf();
+    output.fs().create_dir_all(&output_path)?;
 
     for entry in WalkDir::new(temp_path).min_depth(1) {
         let entry = entry?;
@@ -73,63 +350,163 @@ pub fn copy_template(
             continue;
         }
 
-        if relative_str == "template.json" {
+        if relative_str == "template.json" || is_git_dir(relative_path) {
             continue;
         }
 
         let dest_path = output_path.join(relative_path);
+        copy_entry(&entry, &dest_path, vars, output)?;
+    }
 
-        if entry.file_type().is_dir() {
-            fs::create_dir_all(&dest_path)?;
-        } else {
-            if let Some(parent) = dest_path.parent() {
-                fs::create_dir_all(parent)?;
-            }
+    Ok(())
+}
 
-            // Try to read as UTF-8 for template processing, fall back to binary copy
-            match fs::read_to_string(entry.path()) {
-                Ok(content) => {
-                    let processed = process_template(&content, project_name);
-                    fs::write(&dest_path, processed)?;
-                }
-                Err(_) => {
-                    fs::copy(entry.path(), &dest_path)?;
-                }
-            }
-        }
+/// Local template checkouts carry their own `.git`, which must never be copied.
+pub fn is_git_dir(relative_path: &Path) -> bool {
+    relative_path
+        .components()
+        .next()
+        .is_some_and(|c| c.as_os_str() == ".git")
+}
+
+/// Bytes sniffed when deciding whether a file is binary.
+const BINARY_SNIFF_LEN: usize = 8000;
+
+/// A file is treated as binary if it contains a NUL byte near the start
+/// (the same heuristic git uses) or is not valid UTF-8.
+pub fn is_binary(bytes: &[u8]) -> bool {
+    let sniff = &bytes[..bytes.len().min(BINARY_SNIFF_LEN)];
+    sniff.contains(&0) || std::str::from_utf8(bytes).is_err()
+}
+
+/// Copy a single walked entry to `dest_path`.
+///
+/// Directories are created, symlinks are recreated as symlinks, binary files
+/// are copied byte-for-byte and text files go through `process_template`
+/// without touching their line endings. Unix modes are carried over.
+/// Existing files are handled according to the output's `WriteMode`.
+pub fn copy_entry(
+    entry: &walkdir::DirEntry,
+    dest_path: &Path,
+    vars: &TemplateVars,
+    output: &mut OutputDir,
+) -> Result<()> {
+    let file_type = entry.file_type();
+
+    if file_type.is_dir() {
+        output.prepare_dir(dest_path)?;
+        output.fs().create_dir_all(dest_path)?;
+        return Ok(());
     }
 
-    Ok(())
+    if let Some(parent) = dest_path.parent() {
+        output.fs().create_dir_all(parent)?;
+    }
+
+    if !output.prepare_write(dest_path)? {
+        return Ok(());
+    }
+
+    if file_type.is_symlink() {
+        return copy_symlink(entry, dest_path, vars, output);
+    }
+    write_file(entry.path(), dest_path, vars, output)
 }
 
-pub fn init_git(output_path: &Path) -> Result<()> {
-    Command::new("git")
-        .args(["init"])
-        .current_dir(output_path)
-        .stdout(std::process::Stdio::null())
-        .stderr(std::process::Stdio::null())
-        .status()?;
+/// Write the file at `source` to `dest_path`, substituting placeholders in
+/// text and keeping its Unix mode.
+fn write_file(source: &Path, dest_path: &Path, vars: &TemplateVars, output: &mut OutputDir) -> Result<()> {
+    let bytes = fs::read(source)?;
+    if is_binary(&bytes) {
+        output.fs().write(dest_path, &bytes)?;
+    } else {
+        // Safe: is_binary already rejected invalid UTF-8
+        let content = String::from_utf8(bytes)?;
+        output
+            .fs()
+            .write(dest_path, process_template(&content, vars).as_bytes())?;
+    }
 
-    Command::new("git")
-        .args(["add", "."])
-        .current_dir(output_path)
-        .stdout(std::process::Stdio::null())
-        .stderr(std::process::Stdio::null())
-        .status()?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(source)?.permissions().mode();
+        output.fs().set_mode(dest_path, mode)?;
+    }
 
-    Command::new("git")
-        .args(["commit", "-m", "Initial commit from nullslate"])
-        .current_dir(output_path)
-        .stdout(std::process::Stdio::null())
-        .stderr(std::process::Stdio::null())
-        .status()?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_symlink(
+    entry: &walkdir::DirEntry,
+    dest_path: &Path,
+    _vars: &TemplateVars,
+    output: &mut OutputDir,
+) -> Result<()> {
+    let target = fs::read_link(entry.path())?;
+    output
+        .fs()
+        .symlink(&target, dest_path)
+        .with_context(|| format!("Failed to create symlink {}", dest_path.display()))
+}
+
+#[cfg(not(unix))]
+fn copy_symlink(
+    entry: &walkdir::DirEntry,
+    dest_path: &Path,
+    vars: &TemplateVars,
+    output: &mut OutputDir,
+) -> Result<()> {
+    // No portable symlink creation; fall back to copying what the link points at
+    copy_link_target(entry.path(), dest_path, vars, output)
+}
 
+/// Copy what `link` points at to `dest_path`, recursing into directories.
+#[cfg(any(not(unix), test))]
+fn copy_link_target(link: &Path, dest_path: &Path, vars: &TemplateVars, output: &mut OutputDir) -> Result<()> {
+    for entry in WalkDir::new(link).follow_links(true) {
+        let entry = entry?;
+        let relative = entry.path().strip_prefix(link)?;
+        let dest = if relative.as_os_str().is_empty() {
+            dest_path.to_path_buf()
+        } else {
+            dest_path.join(relative)
+        };
+        if entry.file_type().is_dir() {
+            output.fs().create_dir_all(&dest)?;
+        } else {
+            write_file(entry.path(), &dest, vars, output)?;
+        }
+    }
     Ok(())
 }
 
-pub fn install_deps(output_path: &Path) -> Result<()> {
-    let output = Command::new("bun")
-        .args(["install"])
+/// Install dependencies according to `policy`, frozen to the lockfile when
+/// `frozen` is set. Failures are not fatal: they come back as warnings so
+/// the caller can tell the user how to finish the install manually.
+pub fn install_deps(
+    output_path: &Path,
+    pm: PackageManager,
+    policy: ResolvedPolicy,
+    frozen: bool,
+    managed: &[String],
+) -> Result<Vec<String>> {
+    let mut warnings = Vec::new();
+    let install_args = if frozen {
+        pm.frozen_install_args(output_path)
+    } else {
+        pm.install_args()
+    };
+    let install_cmd = format!("{} {}", pm.program(), install_args.join(" "));
+    let manual_hint = format!(
+        "You can run it manually with: cd {} && {}",
+        output_path.display(),
+        install_cmd
+    );
+
+    let output = Command::new(pm.program())
+        .args(&install_args)
         .current_dir(output_path)
         .stdout(std::process::Stdio::null())
         .stderr(std::process::Stdio::piped())
@@ -138,31 +515,24 @@ pub fn install_deps(output_path: &Path) -> Result<()> {
     match output {
         Ok(out) if !out.status.success() => {
             let stderr = String::from_utf8_lossy(&out.stderr);
-            eprintln!("Warning: bun install failed:\n{}", stderr.trim());
-            eprintln!(
-                "You can run it manually with: cd {} && bun install",
-                output_path.display()
-            );
-            return Ok(());
+            warnings.push(format!("{} failed:\n{}\n{}", install_cmd, stderr.trim(), manual_hint));
+            return Ok(warnings);
         }
         Err(e) => {
-            eprintln!("Warning: Failed to run bun install: {}", e);
-            eprintln!(
-                "You can run it manually with: cd {} && bun install",
-                output_path.display()
-            );
-            return Ok(());
+            warnings.push(format!("Failed to run {}: {}\n{}", install_cmd, e, manual_hint));
+            return Ok(warnings);
         }
         _ => {}
     }
 
-    // Update packages to latest versions
-    let update_output = Command::new("bun")
-        .args([
-            "add",
-            "@thesandybridge/themes@latest",
-            "@thesandybridge/ui@latest",
-        ])
+    if policy != ResolvedPolicy::Latest {
+        return Ok(warnings);
+    }
+
+    let latest: Vec<String> = managed.iter().map(|name| format!("{name}@latest")).collect();
+    let latest: Vec<&str> = latest.iter().map(String::as_str).collect();
+    let update_output = Command::new(pm.program())
+        .args(pm.add_args(&latest))
         .current_dir(output_path)
         .stdout(std::process::Stdio::null())
         .stderr(std::process::Stdio::piped())
@@ -171,37 +541,254 @@ pub fn install_deps(output_path: &Path) -> Result<()> {
     match update_output {
         Ok(out) if !out.status.success() => {
             let stderr = String::from_utf8_lossy(&out.stderr);
-            eprintln!("Warning: Failed to update packages:\n{}", stderr.trim());
+            warnings.push(format!("Failed to update packages:\n{}", stderr.trim()));
         }
         Err(e) => {
-            eprintln!("Warning: Failed to update packages: {}", e);
+            warnings.push(format!("Failed to update packages: {}", e));
         }
         _ => {}
     }
 
-    Ok(())
+    Ok(warnings)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn vars(project_name: &str) -> TemplateVars {
+        TemplateVars::from([("project_name".to_string(), project_name.to_string())])
+    }
+
     #[test]
     fn process_template_replaces_placeholder() {
-        let result = process_template("name: {{project_name}}", "my-app");
+        let result = process_template("name: {{project_name}}", &vars("my-app"));
         assert_eq!(result, "name: my-app");
     }
 
     #[test]
     fn process_template_no_placeholder() {
         let input = "no placeholders here";
-        let result = process_template(input, "my-app");
+        let result = process_template(input, &vars("my-app"));
         assert_eq!(result, input);
     }
 
     #[test]
     fn process_template_multiple_placeholders() {
-        let result = process_template("{{project_name}} and {{project_name}}", "my-app");
+        let result = process_template("{{project_name}} and {{project_name}}", &vars("my-app"));
         assert_eq!(result, "my-app and my-app");
     }
+
+    #[test]
+    fn process_template_custom_variables() {
+        let mut vars = vars("my-app");
+        vars.insert("author".to_string(), "acme".to_string());
+        let result = process_template("{{project_name}} by {{author}}", &vars);
+        assert_eq!(result, "my-app by acme");
+    }
+
+    #[test]
+    fn process_template_is_a_single_pass() {
+        let mut vars = vars("my-app");
+        vars.insert("description".to_string(), "uses {{project_name}}".to_string());
+        let result = process_template("{{description}} {{{project_name}}} {{unknown}}", &vars);
+        assert_eq!(result, "uses {{project_name}} {my-app} {{unknown}}");
+    }
+
+    #[test]
+    fn copy_template_skips_git_dir() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        fs::create_dir(src.path().join(".git")).unwrap();
+        fs::write(src.path().join(".git/HEAD"), "ref").unwrap();
+        fs::write(src.path().join(".gitignore"), "node_modules").unwrap();
+
+        let mut output = OutputDir::new(dest.path(), WriteMode::Create);
+        copy_template(src.path(), &mut output, &vars("my-app"), &[]).unwrap();
+
+        assert!(!dest.path().join(".git").exists());
+        assert!(dest.path().join(".gitignore").exists());
+    }
+
+    #[test]
+    fn is_binary_detects_nul_bytes() {
+        assert!(is_binary(b"PNG\0\x01\x02"));
+        assert!(!is_binary(b"plain text\r\n"));
+    }
+
+    #[test]
+    fn is_binary_detects_invalid_utf8() {
+        assert!(is_binary(&[0xff, 0xfe, 0x41]));
+    }
+
+    #[test]
+    fn copy_template_preserves_line_endings() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        fs::write(src.path().join("README.md"), "# {{project_name}}\r\nbody\r\n").unwrap();
+
+        let mut output = OutputDir::new(dest.path(), WriteMode::Create);
+        copy_template(src.path(), &mut output, &vars("my-app"), &[]).unwrap();
+
+        let out = fs::read(dest.path().join("README.md")).unwrap();
+        assert_eq!(out, b"# my-app\r\nbody\r\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn copy_template_preserves_executable_bit() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        fs::create_dir(src.path().join("scripts")).unwrap();
+        let script = src.path().join("scripts/setup.sh");
+        fs::write(&script, "#!/bin/sh\necho {{project_name}}\n").unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut output = OutputDir::new(dest.path(), WriteMode::Create);
+        copy_template(src.path(), &mut output, &vars("my-app"), &[]).unwrap();
+
+        let mode = fs::metadata(dest.path().join("scripts/setup.sh"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o755);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn copy_template_recreates_symlinks() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        fs::write(src.path().join("AGENTS.md"), "rules").unwrap();
+        std::os::unix::fs::symlink("AGENTS.md", src.path().join("CLAUDE.md")).unwrap();
+
+        let mut output = OutputDir::new(dest.path(), WriteMode::Create);
+        copy_template(src.path(), &mut output, &vars("my-app"), &[]).unwrap();
+
+        let link = dest.path().join("CLAUDE.md");
+        assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_link(&link).unwrap(), Path::new("AGENTS.md"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn link_target_copy_handles_directories() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        fs::create_dir_all(src.path().join("shared/nested")).unwrap();
+        fs::write(src.path().join("shared/nested/a.md"), "{{project_name}}").unwrap();
+        fs::write(src.path().join("notes.md"), "notes").unwrap();
+        std::os::unix::fs::symlink("shared", src.path().join("docs")).unwrap();
+        std::os::unix::fs::symlink("notes.md", src.path().join("NOTES.md")).unwrap();
+
+        let mut output = OutputDir::new(dest.path(), WriteMode::Create);
+        copy_link_target(&src.path().join("docs"), &dest.path().join("docs"), &vars("my-app"), &mut output).unwrap();
+        copy_link_target(&src.path().join("NOTES.md"), &dest.path().join("NOTES.md"), &vars("my-app"), &mut output)
+            .unwrap();
+
+        assert_eq!(fs::read_to_string(dest.path().join("docs/nested/a.md")).unwrap(), "my-app");
+        assert_eq!(fs::read_to_string(dest.path().join("NOTES.md")).unwrap(), "notes");
+    }
+
+    #[test]
+    fn copy_template_copies_binary_verbatim() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        let bytes = b"\x89PNG\r\n\x1a\n\0{{project_name}}";
+        fs::write(src.path().join("logo.png"), bytes).unwrap();
+
+        let mut output = OutputDir::new(dest.path(), WriteMode::Create);
+        copy_template(src.path(), &mut output, &vars("my-app"), &[]).unwrap();
+
+        assert_eq!(fs::read(dest.path().join("logo.png")).unwrap(), bytes);
+    }
+
+    #[test]
+    fn check_output_dir_allows_missing_and_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(check_output_dir(&dir.path().join("new"), WriteMode::Create).is_ok());
+        assert!(check_output_dir(dir.path(), WriteMode::Create).is_ok());
+    }
+
+    #[test]
+    fn check_output_dir_rejects_non_empty_without_mode() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("README.md"), "hi").unwrap();
+        assert!(check_output_dir(dir.path(), WriteMode::Create).is_err());
+        assert!(check_output_dir(dir.path(), WriteMode::Merge).is_ok());
+        assert!(check_output_dir(dir.path(), WriteMode::Force).is_ok());
+    }
+
+    #[test]
+    fn copy_template_merge_keeps_existing_files() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        fs::write(src.path().join("README.md"), "template").unwrap();
+        fs::write(src.path().join("index.html"), "template").unwrap();
+        fs::write(dest.path().join("README.md"), "mine").unwrap();
+
+        let mut output = OutputDir::new(dest.path(), WriteMode::Merge);
+        copy_template(src.path(), &mut output, &vars("my-app"), &[]).unwrap();
+
+        assert_eq!(fs::read_to_string(dest.path().join("README.md")).unwrap(), "mine");
+        assert_eq!(fs::read_to_string(dest.path().join("index.html")).unwrap(), "template");
+        assert!(output.report.was_skipped("README.md"));
+        assert!(output.report.backed_up.is_empty());
+    }
+
+    #[test]
+    fn copy_template_force_backs_up_replaced_files() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        fs::write(src.path().join("README.md"), "template").unwrap();
+        fs::write(dest.path().join("README.md"), "mine").unwrap();
+
+        let mut output = OutputDir::new(dest.path(), WriteMode::Force);
+        copy_template(src.path(), &mut output, &vars("my-app"), &[]).unwrap();
+
+        assert_eq!(fs::read_to_string(dest.path().join("README.md")).unwrap(), "template");
+        let backup_dir = output.report.backup_dir.clone().unwrap();
+        assert!(backup_dir.starts_with(BACKUP_DIR));
+        let backup = dest.path().join(&backup_dir).join("README.md");
+        assert_eq!(fs::read_to_string(backup).unwrap(), "mine");
+        assert_eq!(output.report.backed_up, vec![PathBuf::from("README.md")]);
+
+        // A second run keeps the first run's backups
+        fs::write(dest.path().join("README.md"), "edited").unwrap();
+        let mut output = OutputDir::new(dest.path(), WriteMode::Force);
+        copy_template(src.path(), &mut output, &vars("my-app"), &[]).unwrap();
+        let second = output.report.backup_dir.unwrap();
+        assert_ne!(second, backup_dir);
+        assert_eq!(fs::read_to_string(dest.path().join(second).join("README.md")).unwrap(), "edited");
+        assert_eq!(fs::read_to_string(dest.path().join(backup_dir).join("README.md")).unwrap(), "mine");
+    }
+
+    #[test]
+    fn backup_stamp_is_a_utc_timestamp() {
+        let stamp = backup_stamp();
+        assert_eq!(stamp.len(), 15, "{stamp}");
+        assert!(stamp.starts_with("20"), "{stamp}");
+        assert_eq!(&stamp[8..9], "-");
+    }
+
+    #[test]
+    fn file_where_the_template_has_a_directory() {
+        let src = tempfile::tempdir().unwrap();
+        let dest = tempfile::tempdir().unwrap();
+        fs::create_dir(src.path().join("docs")).unwrap();
+        fs::write(src.path().join("docs/index.md"), "template").unwrap();
+        fs::write(dest.path().join("docs"), "mine").unwrap();
+
+        let mut output = OutputDir::new(dest.path(), WriteMode::Merge);
+        let err = copy_template(src.path(), &mut output, &vars("my-app"), &[]).unwrap_err();
+        assert!(err.to_string().contains("use --force"), "{err}");
+
+        let mut output = OutputDir::new(dest.path(), WriteMode::Force);
+        copy_template(src.path(), &mut output, &vars("my-app"), &[]).unwrap();
+        assert_eq!(fs::read_to_string(dest.path().join("docs/index.md")).unwrap(), "template");
+        let backup = dest.path().join(output.report.backup_dir.unwrap()).join("docs");
+        assert_eq!(fs::read_to_string(backup).unwrap(), "mine");
+    }
 }
@@ -1,6 +1,16 @@
 use anyhow::Result;
 
+use nullslate_cli::events::{self, Event, ServiceStatus, Step, TaskOutcome, TaskTiming};
+use nullslate_cli::naming::ProjectName;
+use nullslate_cli::scaffold::RenderedProject;
+use nullslate_cli::template::CopyReport;
+use nullslate_cli::vfs::DiffEntry;
+use nullslate_cli::{AuthProvider, Database, Language, Orm, PackageManager, ProjectType, ScaffoldReport};
+
 pub fn intro() -> Result<()> {
+    if events::is_jsonl() {
+        return Ok(());
+    }
     cliclack::clear_screen()?;
     cliclack::intro("nullslate")?;
     Ok(())
@@ -9,18 +19,24 @@ pub fn intro() -> Result<()> {
 pub fn prompt_project_name() -> Result<String> {
     let name: String = cliclack::input("Project name")
         .placeholder("my-app")
-        .validate(|input: &String| {
-            let re = regex::Regex::new(r"^[a-z0-9][a-z0-9-]*[a-z0-9]$|^[a-z0-9]$").unwrap();
-            if re.is_match(input) {
-                Ok(())
-            } else {
-                Err("Use lowercase letters, numbers, and hyphens only")
-            }
-        })
+        .validate(|input: &String| ProjectName::parse(input).map(|_| ()))
         .interact()?;
     Ok(name)
 }
 
+/// Offer `suggestion` in place of an invalid name given on the command line.
+pub fn confirm_suggested_name(input: &str, suggestion: &str) -> Result<bool> {
+    if events::is_jsonl() {
+        return Ok(false);
+    }
+    let accepted = cliclack::confirm(format!(
+        "'{input}' isn't a valid project name. Use '{suggestion}' instead?"
+    ))
+    .initial_value(true)
+    .interact()?;
+    Ok(accepted)
+}
+
 #[derive(Clone, PartialEq, Eq)]
 pub enum Feature {
     Auth,
@@ -30,52 +46,152 @@ pub enum Feature {
 
 pub fn prompt_features() -> Result<Vec<Feature>> {
     let features: Vec<Feature> = cliclack::multiselect("Select features")
-        .item(Feature::Auth, "Authentication", "Auth.js with GitHub OAuth")
+        .item(Feature::Auth, "Authentication", "Auth.js with GitHub, Google, Discord, email or credentials")
         .item(Feature::Docs, "Documentation", "MDX docs system")
-        .item(Feature::Db, "Database", "PostgreSQL")
+        .item(Feature::Db, "Database", "PostgreSQL, SQLite or MySQL")
         .initial_values(vec![Feature::Auth])
         .required(false)
         .interact()?;
     Ok(features)
 }
 
-pub fn create_spinner(message: &str) -> cliclack::ProgressBar {
+pub fn prompt_database(choices: &[Database]) -> Result<Database> {
+    let mut prompt = cliclack::select("Which database?");
+    for db in choices {
+        let (label, hint) = match db {
+            Database::None => ("None", ""),
+            Database::Postgres => ("PostgreSQL", "Server database"),
+            Database::Sqlite => ("SQLite", "File-based, no server needed"),
+            Database::Mysql => ("MySQL", "Server database"),
+        };
+        prompt = prompt.item(*db, label, hint);
+    }
+    Ok(prompt.interact()?)
+}
+
+pub fn prompt_orm() -> Result<Orm> {
+    let orm = cliclack::select("How do you want to query it?")
+        .item(Orm::Raw, "Driver only", "Plain SQL through the driver")
+        .item(Orm::Drizzle, "Drizzle", "Schema in TypeScript, drizzle-kit migrations")
+        .item(Orm::Kysely, "Kysely", "Type-safe query builder, kysely-ctl migrations")
+        .interact()?;
+    Ok(orm)
+}
+
+pub fn prompt_auth_providers() -> Result<Vec<AuthProvider>> {
+    let mut prompt = cliclack::multiselect("Which sign-in methods?");
+    for provider in AuthProvider::ALL {
+        let hint = match provider {
+            AuthProvider::Email => "Needs an SMTP server",
+            AuthProvider::Credentials => "You check the password",
+            _ => "",
+        };
+        prompt = prompt.item(provider, provider.label(), hint);
+    }
+    Ok(prompt.initial_values(vec![AuthProvider::GitHub]).interact()?)
+}
+
+/// A cliclack spinner that stays silent in JSON output mode.
+pub struct Spinner(Option<cliclack::ProgressBar>);
+
+impl Spinner {
+    pub fn stop(&self, message: &str) {
+        if let Some(spinner) = &self.0 {
+            spinner.stop(message);
+        }
+    }
+
+    pub fn fail(&self, message: &str) {
+        if let Some(spinner) = &self.0 {
+            spinner.error(message);
+        }
+    }
+}
+
+pub fn step_messages(step: Step) -> (&'static str, &'static str) {
+    match step {
+        Step::FetchTemplate => ("Fetching template...", "Template fetched"),
+        Step::ProcessFiles => ("Processing files...", "Files processed"),
+        Step::GitInit => ("Initializing git...", "Git initialized"),
+        Step::Install => ("Installing dependencies...", "Dependencies installed"),
+    }
+}
+
+pub fn create_spinner(message: &str) -> Spinner {
+    if events::is_jsonl() {
+        return Spinner(None);
+    }
     let spinner = cliclack::spinner();
     spinner.start(message);
-    spinner
+    Spinner(Some(spinner))
 }
 
-pub fn outro_success(project_name: &str, output_path: &std::path::Path, no_install: bool) {
-    let mut next_steps = format!("cd {}", project_name);
+pub fn outro_success(
+    project_name: &str,
+    output_path: &std::path::Path,
+    no_install: bool,
+    pm: PackageManager,
+    report: &ScaffoldReport,
+) {
+    if events::is_jsonl() {
+        return;
+    }
+    let mut next_steps = format!("cd {}", output_path.display());
     if no_install {
-        next_steps.push_str("\n    bun install");
+        next_steps.push_str(&format!("\n    {} install", pm.program()));
     }
-    next_steps.push_str("\n    bun dev");
+    next_steps.push_str(&format!("\n    {}", pm.run_hint("dev")));
 
     let msg = format!(
-        "Created {} at {}\n\n  Next steps:\n    {}",
+        "Created {} at {}\n\n  Next steps:\n    {}{}",
         project_name,
         output_path.display(),
-        next_steps
+        next_steps,
+        details_block(report)
     );
     let _ = cliclack::outro(msg);
 }
 
-pub fn outro_success_fullstack(project_name: &str, output_path: &std::path::Path) {
+pub fn outro_success_fullstack(
+    project_name: &str,
+    output_path: &std::path::Path,
+    report: &ScaffoldReport,
+) {
+    if events::is_jsonl() {
+        return;
+    }
     let msg = format!(
-        "Created {} at {}\n\n  Next steps:\n    cd {}\n    ns dev",
+        "Created {} at {}\n\n  Next steps:\n    cd {}\n    ns dev{}",
         project_name,
         output_path.display(),
-        project_name,
+        output_path.display(),
+        details_block(report)
     );
     let _ = cliclack::outro(msg);
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
-pub enum ProjectType {
-    App,
-    Fullstack,
-    Lib,
+/// Installed versions and auth callback URLs, for the outro.
+fn details_block(report: &ScaffoldReport) -> String {
+    let mut block = String::new();
+    if !report.versions.is_empty() {
+        let lines = report
+            .versions
+            .iter()
+            .map(|(name, version)| format!("    {name} {version}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        block.push_str(&format!("\n\n  Installed versions:\n{lines}"));
+    }
+    if !report.auth_callbacks.is_empty() {
+        let lines = report
+            .auth_callbacks
+            .iter()
+            .map(|url| format!("    {url}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        block.push_str(&format!("\n\n  Auth callback URLs to register:\n{lines}"));
+    }
+    block
 }
 
 pub fn prompt_project_type() -> Result<ProjectType> {
@@ -94,12 +210,6 @@ pub enum LibFeature {
     Testing,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
-pub enum Language {
-    TypeScript,
-    JavaScript,
-}
-
 pub fn prompt_language() -> Result<Language> {
     let lang: Language = cliclack::select("Language")
         .item(Language::TypeScript, "TypeScript", "Recommended")
@@ -118,22 +228,217 @@ pub fn prompt_lib_features() -> Result<Vec<LibFeature>> {
     Ok(features)
 }
 
-pub fn outro_success_lib(project_name: &str, output_path: &std::path::Path, no_install: bool) {
-    let mut next_steps = format!("cd {}", project_name);
+pub fn outro_success_lib(
+    project_name: &str,
+    output_path: &std::path::Path,
+    no_install: bool,
+    pm: PackageManager,
+    report: &ScaffoldReport,
+) {
+    if events::is_jsonl() {
+        return;
+    }
+    let mut next_steps = format!("cd {}", output_path.display());
     if no_install {
-        next_steps.push_str("\n    bun install");
+        next_steps.push_str(&format!("\n    {} install", pm.program()));
     }
-    next_steps.push_str("\n    bun run build");
+    next_steps.push_str(&format!("\n    {}", pm.run_hint("build")));
 
     let msg = format!(
-        "Created {} at {}\n\n  Next steps:\n    {}",
+        "Created {} at {}\n\n  Next steps:\n    {}{}",
         project_name,
         output_path.display(),
-        next_steps
+        next_steps,
+        details_block(report)
+    );
+    let _ = cliclack::outro(msg);
+}
+
+pub fn log_info(message: &str) {
+    if events::is_jsonl() {
+        return;
+    }
+    let _ = cliclack::log::info(message);
+}
+
+pub fn log_success(message: &str) {
+    if events::is_jsonl() {
+        return;
+    }
+    let _ = cliclack::log::success(message);
+}
+
+pub fn log_warning(message: &str) {
+    if events::is_jsonl() {
+        return;
+    }
+    let _ = cliclack::log::warning(message);
+}
+
+pub fn report_copy(report: &CopyReport) {
+    if events::is_jsonl() {
+        return;
+    }
+    if !report.skipped.is_empty() {
+        let list = report
+            .skipped
+            .iter()
+            .map(|p| format!("  {}", p.display()))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let _ = cliclack::log::warning(format!(
+            "Kept {} existing file(s):\n{}",
+            report.skipped.len(),
+            list
+        ));
+    }
+    if let Some(dir) = &report.backup_dir {
+        let _ = cliclack::log::info(format!(
+            "Replaced {} file(s), originals saved in {}/",
+            report.backed_up.len(),
+            dir.display()
+        ));
+    }
+}
+
+pub fn outro_dry_run(rendered: &RenderedProject) {
+    if events::is_jsonl() {
+        return;
+    }
+    let lines: Vec<String> = rendered
+        .diff()
+        .iter()
+        .map(|entry| match entry {
+            DiffEntry::Added(path) => format!("  + {}", path.display()),
+            DiffEntry::Modified(path) => format!("  ~ {}", path.display()),
+            DiffEntry::Unchanged(path) => format!("  = {}", path.display()),
+        })
+        .collect();
+    let _ = cliclack::log::info(lines.join("\n"));
+    let files = &rendered.files;
+    if !files.skipped.is_empty() {
+        let kept: Vec<String> = files.skipped.iter().map(|p| format!("  {}", p.display())).collect();
+        let _ = cliclack::log::warning(format!("Would keep {} existing file(s):\n{}", kept.len(), kept.join("\n")));
+    }
+    if let Some(dir) = &files.backup_dir {
+        let _ = cliclack::log::info(format!(
+            "Would replace {} file(s), saving the originals in {}/",
+            files.backed_up.len(),
+            dir.display()
+        ));
+    }
+
+    let msg = format!(
+        "Dry run: {} file(s) would be written to {} (content hash {})",
+        lines.len(),
+        rendered.output_path.display(),
+        &rendered.hash()[..12]
     );
     let _ = cliclack::outro(msg);
 }
 
 pub fn outro_cancel(message: &str) {
+    if events::is_jsonl() {
+        return;
+    }
     let _ = cliclack::outro_cancel(message);
 }
+
+/// Print what `ns dev`, `ns build` and `ns run` report besides the output
+/// of the processes they run. Everything goes to stderr, next to that
+/// output.
+pub fn print_run_event(event: &Event) {
+    match event {
+        Event::ServicesStarted { services } => eprint!("{}", status_table(services)),
+        Event::ServicesStopping { exited, stopping } => {
+            eprintln!("{exited} exited; stopping {}", stopping.join(", "));
+        }
+        Event::CacheHit { task, restored } => {
+            let note = if *restored { "outputs restored" } else { "outputs up to date" };
+            eprintln!("{task}: cache hit, {note}");
+        }
+        Event::TasksSummary {
+            tasks,
+            total_ms,
+            cache_hits,
+            cache_misses,
+        } => {
+            if tasks.len() > 1 {
+                eprint!("{}", timing_table(tasks, *total_ms));
+            }
+            if !cache_hits.is_empty() || *cache_misses > 0 {
+                let plural = |n: usize, one: &str, many: &str| if n == 1 { one } else { many }.to_string();
+                let mut summary = format!("Cache: {} {}", cache_hits.len(), plural(cache_hits.len(), "hit", "hits"));
+                if !cache_hits.is_empty() {
+                    summary.push_str(&format!(" ({})", cache_hits.join(", ")));
+                }
+                eprintln!("{summary}, {cache_misses} {}", plural(*cache_misses, "miss", "misses"));
+            }
+        }
+        Event::Warning { message } => eprintln!("Warning: {message}"),
+        _ => {}
+    }
+}
+
+/// The services once everything has started: whether each is running, was
+/// ready after its check, or already exited, and the ports it listens on.
+fn status_table(services: &[ServiceStatus]) -> String {
+    let rows: Vec<[String; 3]> = services
+        .iter()
+        .map(|service| {
+            let status = match (service.exited, service.ready_ms) {
+                (true, _) => match service.code {
+                    Some(code) => format!("exited ({code})"),
+                    None => "exited (signal)".to_string(),
+                },
+                (false, Some(ms)) => format!("ready in {:.1}s", ms as f64 / 1000.0),
+                (false, None) => "running".to_string(),
+            };
+            let ports = service
+                .ports
+                .iter()
+                .map(|port| format!("localhost:{port}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            [service.service.clone(), status, ports]
+        })
+        .collect();
+    let header = ["SERVICE".to_string(), "STATUS".to_string(), "PORTS".to_string()];
+    let widths: Vec<usize> = (0..3)
+        .map(|col| rows.iter().chain([&header]).map(|row| row[col].len()).max().unwrap_or(0))
+        .collect();
+    let mut table = String::new();
+    for row in [&header].into_iter().chain(&rows) {
+        let line = format!("{:w0$}  {:w1$}  {}", row[0], row[1], row[2], w0 = widths[0], w1 = widths[1]);
+        table.push_str(line.trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+/// How long each task of a run took, or why it didn't finish, and the
+/// total, shown when the run ends.
+fn timing_table(tasks: &[TaskTiming], total_ms: u64) -> String {
+    let secs = |ms: Option<u64>| format!("{:.1}s", ms.unwrap_or(0) as f64 / 1000.0);
+    let rows: Vec<[String; 2]> = tasks
+        .iter()
+        .map(|task| {
+            let time = match task.outcome {
+                TaskOutcome::Finished => secs(task.elapsed_ms),
+                TaskOutcome::Cached => "cached".to_string(),
+                TaskOutcome::Failed => format!("failed after {}", secs(task.elapsed_ms)),
+                TaskOutcome::Cancelled => format!("cancelled after {}", secs(task.elapsed_ms)),
+                TaskOutcome::NotStarted => "not started".to_string(),
+            };
+            [task.task.clone(), time]
+        })
+        .chain([["total".to_string(), secs(Some(total_ms))]])
+        .collect();
+    let header = ["TASK".to_string(), "TIME".to_string()];
+    let width = rows.iter().chain([&header]).map(|row| row[0].len()).max().unwrap_or(0);
+    let mut table = String::new();
+    for row in [&header].into_iter().chain(&rows) {
+        table.push_str(&format!("{:width$}  {}\n", row[0], row[1]));
+    }
+    table
+}
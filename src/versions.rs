@@ -0,0 +1,257 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::pm::PackageManager;
+use crate::vfs::FileSystem;
+
+/// Ecosystem packages whose versions are reported (and, with `Latest`,
+/// upgraded) when the template doesn't list its own.
+pub const DEFAULT_MANAGED_PACKAGES: &[&str] = &["@thesandybridge/themes", "@thesandybridge/ui"];
+
+const DEPENDENCY_SECTIONS: &[&str] = &["dependencies", "devDependencies", "peerDependencies"];
+
+/// How dependency versions are chosen at scaffold time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum VersionPolicy {
+    /// Use the template's lockfile if it ships one, else its pins, else the
+    /// ranges already in package.json.
+    #[default]
+    Auto,
+    /// Install exactly what the template's lockfile says.
+    Lockfile,
+    /// Write the exact versions from template.json into package.json.
+    Pinned,
+    /// Upgrade managed packages to `@latest` after install.
+    Latest,
+}
+
+/// The policy actually applied once the template has been inspected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResolvedPolicy {
+    Lockfile,
+    Pinned,
+    Ranges,
+    Latest,
+}
+
+impl ResolvedPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ResolvedPolicy::Lockfile => "lockfile",
+            ResolvedPolicy::Pinned => "pinned",
+            ResolvedPolicy::Ranges => "ranges",
+            ResolvedPolicy::Latest => "latest",
+        }
+    }
+}
+
+/// The optional `template.json` at the template root.
+#[derive(Debug, Default, Deserialize)]
+pub struct TemplateConfig {
+    /// Managed packages and the exact versions to pin them to.
+    #[serde(default)]
+    pub packages: BTreeMap<String, String>,
+}
+
+impl TemplateConfig {
+    pub fn load(template_path: &Path) -> Result<TemplateConfig> {
+        let path = template_path.join("template.json");
+        if !path.exists() {
+            return Ok(TemplateConfig::default());
+        }
+        let content = fs::read_to_string(&path)?;
+        serde_json::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    pub fn managed_packages(&self) -> Vec<String> {
+        if self.packages.is_empty() {
+            DEFAULT_MANAGED_PACKAGES.iter().map(|p| p.to_string()).collect()
+        } else {
+            self.packages.keys().cloned().collect()
+        }
+    }
+}
+
+impl VersionPolicy {
+    pub fn resolve(
+        self,
+        template_path: &Path,
+        config: &TemplateConfig,
+        pm: PackageManager,
+    ) -> Result<ResolvedPolicy> {
+        let has_lockfile = pm
+            .lockfiles()
+            .iter()
+            .any(|lock| template_path.join(lock).exists());
+
+        Ok(match self {
+            VersionPolicy::Auto if has_lockfile => ResolvedPolicy::Lockfile,
+            VersionPolicy::Auto if !config.packages.is_empty() => ResolvedPolicy::Pinned,
+            VersionPolicy::Auto => ResolvedPolicy::Ranges,
+            VersionPolicy::Lockfile if has_lockfile => ResolvedPolicy::Lockfile,
+            VersionPolicy::Lockfile => {
+                anyhow::bail!("Template does not ship a {} lockfile", pm.name())
+            }
+            VersionPolicy::Pinned if !config.packages.is_empty() => ResolvedPolicy::Pinned,
+            VersionPolicy::Pinned => {
+                anyhow::bail!("Template does not pin any package versions in template.json")
+            }
+            VersionPolicy::Latest => ResolvedPolicy::Latest,
+        })
+    }
+}
+
+/// Whether two package.json documents declare different dependencies. A
+/// lockfile made for `before` can't be installed frozen against `after`.
+pub fn dependencies_changed(before: &str, after: &str) -> bool {
+    let sections = |content: &str| -> Option<Vec<serde_json::Value>> {
+        let package: serde_json::Value = serde_json::from_str(content).ok()?;
+        Some(DEPENDENCY_SECTIONS.iter().map(|s| package[*s].clone()).collect())
+    };
+    match (sections(before), sections(after)) {
+        (Some(before), Some(after)) => before != after,
+        _ => true,
+    }
+}
+
+/// Rewrite the version of every pinned package already present in
+/// package.json. Packages the template doesn't depend on are left out.
+pub fn apply_pins(
+    fs: &mut dyn FileSystem,
+    dir: &Path,
+    pins: &BTreeMap<String, String>,
+) -> Result<()> {
+    let package_json_path = dir.join("package.json");
+    let content = fs.read_to_string(&package_json_path)?;
+    let mut package: serde_json::Value = serde_json::from_str(&content)?;
+
+    for section in DEPENDENCY_SECTIONS {
+        if let Some(deps) = package.get_mut(*section).and_then(|d| d.as_object_mut()) {
+            for (name, version) in pins {
+                if let Some(entry) = deps.get_mut(name) {
+                    *entry = serde_json::Value::String(version.clone());
+                }
+            }
+        }
+    }
+
+    let formatted = serde_json::to_string_pretty(&package)?;
+    fs.write(&package_json_path, formatted.as_bytes())?;
+    Ok(())
+}
+
+/// Versions actually installed in `dir/node_modules` for the given packages.
+pub fn installed_versions(dir: &Path, packages: &[String]) -> BTreeMap<String, String> {
+    packages
+        .iter()
+        .filter_map(|name| {
+            let manifest = dir.join("node_modules").join(name).join("package.json");
+            let content = fs::read_to_string(manifest).ok()?;
+            let value: serde_json::Value = serde_json::from_str(&content).ok()?;
+            let version = value.get("version")?.as_str()?.to_string();
+            Some((name.clone(), version))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vfs::MemoryFs;
+
+    fn pinned_config() -> TemplateConfig {
+        TemplateConfig {
+            packages: BTreeMap::from([("@thesandybridge/ui".to_string(), "1.4.2".to_string())]),
+        }
+    }
+
+    #[test]
+    fn auto_prefers_lockfile() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("bun.lock"), "").unwrap();
+        let policy = VersionPolicy::Auto
+            .resolve(dir.path(), &pinned_config(), PackageManager::Bun)
+            .unwrap();
+        assert_eq!(policy, ResolvedPolicy::Lockfile);
+    }
+
+    #[test]
+    fn auto_falls_back_to_pins_then_ranges() {
+        let dir = tempfile::tempdir().unwrap();
+        let pm = PackageManager::Bun;
+        assert_eq!(
+            VersionPolicy::Auto.resolve(dir.path(), &pinned_config(), pm).unwrap(),
+            ResolvedPolicy::Pinned
+        );
+        assert_eq!(
+            VersionPolicy::Auto
+                .resolve(dir.path(), &TemplateConfig::default(), pm)
+                .unwrap(),
+            ResolvedPolicy::Ranges
+        );
+    }
+
+    #[test]
+    fn explicit_lockfile_requires_matching_lockfile() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("bun.lock"), "").unwrap();
+        let result = VersionPolicy::Lockfile.resolve(
+            dir.path(),
+            &TemplateConfig::default(),
+            PackageManager::Pnpm,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn dependencies_changed_ignores_other_fields() {
+        let before = r#"{"name": "{{project_name}}", "scripts": {"dev": "vite"}, "dependencies": {"react": "^19"}}"#;
+        let renamed = r#"{"name": "my-app", "dependencies": {"react": "^19"}}"#;
+        let pinned = r#"{"name": "my-app", "dependencies": {"react": "19.1.0"}}"#;
+        assert!(!dependencies_changed(before, renamed));
+        assert!(dependencies_changed(before, pinned));
+        assert!(dependencies_changed(before, "not json"));
+    }
+
+    #[test]
+    fn apply_pins_rewrites_existing_entries_only() {
+        let mut mem = MemoryFs::new();
+        let dir = Path::new("app");
+        mem.create_dir_all(dir).unwrap();
+        mem.write(
+            &dir.join("package.json"),
+            br#"{"dependencies": {"@thesandybridge/ui": "^1.0.0", "react": "^19"}}"#,
+        )
+        .unwrap();
+
+        let pins = BTreeMap::from([
+            ("@thesandybridge/ui".to_string(), "1.4.2".to_string()),
+            ("@thesandybridge/themes".to_string(), "2.0.0".to_string()),
+        ]);
+        apply_pins(&mut mem, dir, &pins).unwrap();
+
+        let package: serde_json::Value =
+            serde_json::from_str(&mem.read_to_string(&dir.join("package.json")).unwrap()).unwrap();
+        assert_eq!(package["dependencies"]["@thesandybridge/ui"], "1.4.2");
+        assert_eq!(package["dependencies"]["react"], "^19");
+        assert!(package["dependencies"].get("@thesandybridge/themes").is_none());
+    }
+
+    #[test]
+    fn installed_versions_reads_node_modules() {
+        let dir = tempfile::tempdir().unwrap();
+        let pkg_dir = dir.path().join("node_modules/@thesandybridge/ui");
+        fs::create_dir_all(&pkg_dir).unwrap();
+        fs::write(pkg_dir.join("package.json"), r#"{"version": "1.4.2"}"#).unwrap();
+
+        let versions = installed_versions(
+            dir.path(),
+            &["@thesandybridge/ui".to_string(), "@thesandybridge/themes".to_string()],
+        );
+        assert_eq!(versions.len(), 1);
+        assert_eq!(versions["@thesandybridge/ui"], "1.4.2");
+    }
+}
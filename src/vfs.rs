@@ -0,0 +1,537 @@
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The subset of filesystem operations the scaffold pipeline performs on its
+/// output. Template sources are always read from disk; only the output side
+/// goes through this trait.
+pub trait FileSystem {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+    fn write(&mut self, path: &Path, contents: &[u8]) -> io::Result<()>;
+    fn create_dir_all(&mut self, path: &Path) -> io::Result<()>;
+    /// Like `symlink_metadata().is_ok()`: dangling symlinks exist.
+    fn exists(&self, path: &Path) -> bool;
+    /// Whether `path` is a directory, following symlinks.
+    fn is_dir(&self, path: &Path) -> bool;
+    fn rename(&mut self, from: &Path, to: &Path) -> io::Result<()>;
+    fn symlink(&mut self, target: &Path, link: &Path) -> io::Result<()>;
+    /// Apply Unix permission bits. A no-op where modes don't exist.
+    fn set_mode(&mut self, path: &Path, mode: u32) -> io::Result<()>;
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        String::from_utf8(self.read(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+impl<T: FileSystem + ?Sized> FileSystem for &mut T {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        (**self).read(path)
+    }
+
+    fn write(&mut self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        (**self).write(path, contents)
+    }
+
+    fn create_dir_all(&mut self, path: &Path) -> io::Result<()> {
+        (**self).create_dir_all(path)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        (**self).exists(path)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        (**self).is_dir(path)
+    }
+
+    fn rename(&mut self, from: &Path, to: &Path) -> io::Result<()> {
+        (**self).rename(from, to)
+    }
+
+    fn symlink(&mut self, target: &Path, link: &Path) -> io::Result<()> {
+        (**self).symlink(target, link)
+    }
+
+    fn set_mode(&mut self, path: &Path, mode: u32) -> io::Result<()> {
+        (**self).set_mode(path, mode)
+    }
+}
+
+/// Writes straight to disk.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RealFs;
+
+impl FileSystem for RealFs {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn write(&mut self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        fs::write(path, contents)
+    }
+
+    fn create_dir_all(&mut self, path: &Path) -> io::Result<()> {
+        fs::create_dir_all(path)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        fs::symlink_metadata(path).is_ok()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+
+    fn rename(&mut self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    #[cfg(unix)]
+    fn symlink(&mut self, target: &Path, link: &Path) -> io::Result<()> {
+        std::os::unix::fs::symlink(target, link)
+    }
+
+    #[cfg(not(unix))]
+    fn symlink(&mut self, _target: &Path, _link: &Path) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "symlinks are only supported on Unix",
+        ))
+    }
+
+    #[cfg(unix)]
+    fn set_mode(&mut self, path: &Path, mode: u32) -> io::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))
+    }
+
+    #[cfg(not(unix))]
+    fn set_mode(&mut self, _path: &Path, _mode: u32) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Node {
+    Dir,
+    File { contents: Vec<u8>, mode: u32 },
+    Symlink(PathBuf),
+}
+
+const DEFAULT_FILE_MODE: u32 = 0o644;
+
+/// An in-memory output tree, for dry runs, previews and tests. Once rendered
+/// it can be inspected, diffed against disk, hashed or flushed to a real
+/// filesystem.
+#[derive(Debug, Default, Clone)]
+pub struct MemoryFs {
+    nodes: BTreeMap<PathBuf, Node>,
+    /// Read through to disk for paths that haven't been written.
+    over_disk: bool,
+    /// Disk paths moved away, which no longer read through.
+    moved: BTreeSet<PathBuf>,
+}
+
+/// How a file in a `MemoryFs` compares to what is on disk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffEntry {
+    Added(PathBuf),
+    Modified(PathBuf),
+    Unchanged(PathBuf),
+}
+
+impl MemoryFs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A layer over the real filesystem: paths that haven't been written
+    /// read from disk, and nothing is ever written there. Only written
+    /// paths show up in `nodes`, `files`, `hash` and `diff_against_disk`.
+    pub fn over_disk() -> Self {
+        Self {
+            over_disk: true,
+            ..Self::default()
+        }
+    }
+
+    /// Whether reads of `path` fall through to disk.
+    fn on_disk(&self, path: &Path) -> bool {
+        self.over_disk
+            && !self.nodes.contains_key(path)
+            && !self.moved.iter().any(|m| path.starts_with(m))
+            && fs::symlink_metadata(path).is_ok()
+    }
+
+    /// Copy the disk tree at `path` into memory.
+    fn load_from_disk(&mut self, path: &Path) -> io::Result<()> {
+        for entry in walkdir::WalkDir::new(path) {
+            let entry = entry.map_err(io::Error::other)?;
+            let node = if entry.file_type().is_symlink() {
+                Node::Symlink(fs::read_link(entry.path())?)
+            } else if entry.file_type().is_dir() {
+                Node::Dir
+            } else {
+                Node::File {
+                    contents: fs::read(entry.path())?,
+                    mode: disk_mode(entry.path())?,
+                }
+            };
+            self.nodes.insert(entry.path().to_path_buf(), node);
+        }
+        Ok(())
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = (&Path, &Node)> {
+        self.nodes.iter().map(|(p, n)| (p.as_path(), n))
+    }
+
+    /// Paths of regular files and symlinks, in sorted order.
+    pub fn files(&self) -> impl Iterator<Item = &Path> {
+        self.nodes
+            .iter()
+            .filter(|(_, n)| !matches!(n, Node::Dir))
+            .map(|(p, _)| p.as_path())
+    }
+
+    /// A stable SHA-256 over every path (relative to `root`), mode, symlink
+    /// target and file body under `root`.
+    pub fn hash(&self, root: &Path) -> String {
+        let mut hasher = Sha256::new();
+        for (path, node) in &self.nodes {
+            let Ok(relative) = path.strip_prefix(root) else {
+                continue;
+            };
+            hasher.update(relative.to_string_lossy().as_bytes());
+            hasher.update([0]);
+            match node {
+                Node::Dir => hasher.update(b"d"),
+                Node::File { contents, mode } => {
+                    hasher.update(b"f");
+                    hasher.update(mode.to_le_bytes());
+                    hasher.update((contents.len() as u64).to_le_bytes());
+                    hasher.update(contents);
+                }
+                Node::Symlink(target) => {
+                    hasher.update(b"l");
+                    hasher.update(target.to_string_lossy().as_bytes());
+                }
+            }
+            hasher.update([0]);
+        }
+        hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    /// Compare every file and symlink against the same path on disk.
+    pub fn diff_against_disk(&self) -> Vec<DiffEntry> {
+        self.nodes
+            .iter()
+            .filter_map(|(path, node)| {
+                let on_disk = match node {
+                    Node::Dir => return None,
+                    Node::File { contents, .. } => match fs::read(path) {
+                        Ok(existing) => Some(existing == *contents),
+                        Err(_) => None,
+                    },
+                    Node::Symlink(target) => match fs::read_link(path) {
+                        Ok(existing) => Some(existing == *target),
+                        Err(_) => None,
+                    },
+                };
+                Some(match on_disk {
+                    None => DiffEntry::Added(path.clone()),
+                    Some(true) => DiffEntry::Unchanged(path.clone()),
+                    Some(false) => DiffEntry::Modified(path.clone()),
+                })
+            })
+            .collect()
+    }
+
+    /// Replay the whole tree onto another filesystem.
+    pub fn flush_to(&self, target: &mut dyn FileSystem) -> io::Result<()> {
+        // BTreeMap order guarantees parents come before children
+        for (path, node) in &self.nodes {
+            match node {
+                Node::Dir => target.create_dir_all(path)?,
+                Node::File { contents, mode } => {
+                    target.write(path, contents)?;
+                    target.set_mode(path, *mode)?;
+                }
+                Node::Symlink(link_target) => target.symlink(link_target, path)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn ensure_parent(&mut self, path: &Path) -> io::Result<()> {
+        match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => match self.nodes.get(parent) {
+                Some(Node::Dir) => Ok(()),
+                None if self.on_disk(parent) && parent.is_dir() => Ok(()),
+                Some(_) => Err(io::Error::other(format!(
+                    "{} is not a directory",
+                    parent.display()
+                ))),
+                None => Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("{} does not exist", parent.display()),
+                )),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    fn not_found(path: &Path) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} does not exist", path.display()),
+        )
+    }
+}
+
+#[cfg(unix)]
+fn disk_mode(path: &Path) -> io::Result<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Ok(fs::metadata(path)?.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+fn disk_mode(_path: &Path) -> io::Result<u32> {
+    Ok(DEFAULT_FILE_MODE)
+}
+
+impl FileSystem for MemoryFs {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        if self.on_disk(path) {
+            return fs::read(path);
+        }
+        match self.nodes.get(path) {
+            Some(Node::File { contents, .. }) => Ok(contents.clone()),
+            Some(Node::Symlink(target)) => {
+                let resolved = path.parent().unwrap_or(Path::new("")).join(target);
+                self.read(&resolved)
+            }
+            Some(Node::Dir) => Err(io::Error::other(format!(
+                "{} is a directory",
+                path.display()
+            ))),
+            None => Err(Self::not_found(path)),
+        }
+    }
+
+    fn write(&mut self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        self.ensure_parent(path)?;
+        let mode = match self.nodes.get(path) {
+            Some(Node::File { mode, .. }) => *mode,
+            _ => DEFAULT_FILE_MODE,
+        };
+        self.nodes.insert(
+            path.to_path_buf(),
+            Node::File {
+                contents: contents.to_vec(),
+                mode,
+            },
+        );
+        Ok(())
+    }
+
+    fn create_dir_all(&mut self, path: &Path) -> io::Result<()> {
+        for ancestor in path.ancestors() {
+            if ancestor.as_os_str().is_empty() {
+                break;
+            }
+            match self.nodes.get(ancestor) {
+                Some(Node::Dir) => {}
+                None if self.on_disk(ancestor) && !ancestor.is_dir() => {
+                    return Err(io::Error::other(format!(
+                        "{} is not a directory",
+                        ancestor.display()
+                    )))
+                }
+                Some(_) => {
+                    return Err(io::Error::other(format!(
+                        "{} is not a directory",
+                        ancestor.display()
+                    )))
+                }
+                None => {
+                    self.nodes.insert(ancestor.to_path_buf(), Node::Dir);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.nodes.contains_key(path) || self.on_disk(path)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        if self.on_disk(path) {
+            return path.is_dir();
+        }
+        match self.nodes.get(path) {
+            Some(Node::Dir) => true,
+            Some(Node::Symlink(target)) => self.is_dir(&path.parent().unwrap_or(Path::new("")).join(target)),
+            _ => false,
+        }
+    }
+
+    fn rename(&mut self, from: &Path, to: &Path) -> io::Result<()> {
+        if self.on_disk(from) {
+            self.load_from_disk(from)?;
+            self.moved.insert(from.to_path_buf());
+        }
+        if !self.nodes.contains_key(from) {
+            return Err(Self::not_found(from));
+        }
+        self.ensure_parent(to)?;
+        let moved: Vec<PathBuf> = self
+            .nodes
+            .keys()
+            .filter(|p| p.starts_with(from))
+            .cloned()
+            .collect();
+        for old in moved {
+            let node = self.nodes.remove(&old).expect("key was just listed");
+            let suffix = old.strip_prefix(from).expect("filtered by starts_with");
+            self.nodes.insert(to.join(suffix), node);
+        }
+        Ok(())
+    }
+
+    fn symlink(&mut self, target: &Path, link: &Path) -> io::Result<()> {
+        self.ensure_parent(link)?;
+        if self.exists(link) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already exists", link.display()),
+            ));
+        }
+        self.nodes
+            .insert(link.to_path_buf(), Node::Symlink(target.to_path_buf()));
+        Ok(())
+    }
+
+    fn set_mode(&mut self, path: &Path, new_mode: u32) -> io::Result<()> {
+        if self.on_disk(path) {
+            self.load_from_disk(path)?;
+        }
+        match self.nodes.get_mut(path) {
+            Some(Node::File { mode, .. }) => {
+                *mode = new_mode;
+                Ok(())
+            }
+            Some(_) => Ok(()),
+            None => Err(Self::not_found(path)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_fs_write_requires_parent() {
+        let mut mem = MemoryFs::new();
+        assert!(mem.write(Path::new("app/package.json"), b"{}").is_err());
+        mem.create_dir_all(Path::new("app")).unwrap();
+        mem.write(Path::new("app/package.json"), b"{}").unwrap();
+        assert_eq!(mem.read_to_string(Path::new("app/package.json")).unwrap(), "{}");
+    }
+
+    #[test]
+    fn memory_fs_rename_moves_subtree() {
+        let mut mem = MemoryFs::new();
+        mem.create_dir_all(Path::new("app/src")).unwrap();
+        mem.write(Path::new("app/src/main.ts"), b"x").unwrap();
+        mem.create_dir_all(Path::new("backup")).unwrap();
+        mem.rename(Path::new("app/src"), Path::new("backup/src")).unwrap();
+
+        assert!(!mem.exists(Path::new("app/src/main.ts")));
+        assert!(mem.exists(Path::new("backup/src/main.ts")));
+    }
+
+    #[test]
+    fn memory_fs_hash_tracks_content_and_mode() {
+        let mut a = MemoryFs::new();
+        a.write(Path::new("run.sh"), b"echo").unwrap();
+        let mut b = a.clone();
+        let root = Path::new("");
+        assert_eq!(a.hash(root), b.hash(root));
+
+        b.set_mode(Path::new("run.sh"), 0o755).unwrap();
+        assert_ne!(a.hash(root), b.hash(root));
+
+        a.write(Path::new("run.sh"), b"echo hi").unwrap();
+        assert_ne!(a.hash(root), MemoryFs::new().hash(root));
+    }
+
+    #[test]
+    fn memory_fs_hash_is_relative_to_root() {
+        let mut a = MemoryFs::new();
+        a.create_dir_all(Path::new("one")).unwrap();
+        a.write(Path::new("one/README.md"), b"hi").unwrap();
+        let mut b = MemoryFs::new();
+        b.create_dir_all(Path::new("two")).unwrap();
+        b.write(Path::new("two/README.md"), b"hi").unwrap();
+
+        assert_eq!(a.hash(Path::new("one")), b.hash(Path::new("two")));
+    }
+
+    #[test]
+    fn memory_fs_over_disk_reads_through_without_writing() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join("src")).unwrap();
+        fs::write(root.join("src/main.ts"), "mine").unwrap();
+
+        let mut mem = MemoryFs::over_disk();
+        assert!(mem.exists(&root.join("src/main.ts")));
+        assert!(mem.is_dir(&root.join("src")));
+        mem.write(&root.join("src/new.ts"), b"new").unwrap();
+        mem.create_dir_all(&root.join("backup")).unwrap();
+        mem.rename(&root.join("src"), &root.join("backup/src")).unwrap();
+
+        assert!(!mem.exists(&root.join("src/main.ts")));
+        assert_eq!(mem.read_to_string(&root.join("backup/src/main.ts")).unwrap(), "mine");
+        assert_eq!(fs::read_to_string(root.join("src/main.ts")).unwrap(), "mine");
+        assert!(!root.join("src/new.ts").exists());
+        assert!(!root.join("backup").exists());
+    }
+
+    #[test]
+    fn memory_fs_flush_and_diff() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("app");
+        let mut mem = MemoryFs::new();
+        mem.create_dir_all(&root).unwrap();
+        mem.write(&root.join("README.md"), b"hello").unwrap();
+
+        assert_eq!(
+            mem.diff_against_disk(),
+            vec![DiffEntry::Added(root.join("README.md"))]
+        );
+
+        mem.flush_to(&mut RealFs).unwrap();
+        assert_eq!(fs::read(root.join("README.md")).unwrap(), b"hello");
+        assert_eq!(
+            mem.diff_against_disk(),
+            vec![DiffEntry::Unchanged(root.join("README.md"))]
+        );
+
+        fs::write(root.join("README.md"), "changed").unwrap();
+        assert_eq!(
+            mem.diff_against_disk(),
+            vec![DiffEntry::Modified(root.join("README.md"))]
+        );
+    }
+}